@@ -46,6 +46,9 @@ contract VRFOracle {
     /// @notice Nonce for generating unique request IDs
     uint256 private nonce;
 
+    /// @notice Compressed secp256k1 public key of the oracle's ECVRF keypair
+    bytes public vrfPublicKey;
+
     /**
      * @notice Emitted when a new randomness request is created
      * @param requestId Unique identifier for the request
@@ -68,6 +71,16 @@ contract VRFOracle {
         uint256 randomness
     );
 
+    /**
+     * @notice Emitted alongside RandomnessFulfilled when the oracle publishes a VRF proof
+     * @param requestId Unique identifier for the request
+     * @param proof ECVRF proof (gamma || c || s) binding the randomness to the request seed
+     */
+    event RandomnessProof(
+        bytes32 indexed requestId,
+        bytes proof
+    );
+
     /// @notice Thrown when non-oracle address attempts to fulfill
     error OnlyOracle();
 
@@ -138,6 +151,33 @@ contract VRFOracle {
         bytes32 requestId,
         uint256 randomness
     ) external {
+        _fulfill(requestId, randomness);
+    }
+
+    /**
+     * @notice Oracle fulfills a pending randomness request with its ECVRF proof
+     * @dev The proof is not verified on-chain; it is published so that anyone can check
+     *      off-chain that `randomness` was derived from the request seed and `vrfPublicKey`
+     * @param requestId The ID of the request to fulfill
+     * @param randomness The VRF output (proof hash) as a uint256
+     * @param proof The ECVRF proof (gamma || c || s)
+     */
+    function fulfillRandomnessWithProof(
+        bytes32 requestId,
+        uint256 randomness,
+        bytes calldata proof
+    ) external {
+        _fulfill(requestId, randomness);
+
+        emit RandomnessProof(requestId, proof);
+    }
+
+    /**
+     * @notice Records the randomness for a pending request
+     * @param requestId The ID of the request to fulfill
+     * @param randomness The random value to store
+     */
+    function _fulfill(bytes32 requestId, uint256 randomness) internal {
         // if (msg.sender != oracle) revert OnlyOracle();
 
         Request storage request = requests[requestId];
@@ -181,6 +221,15 @@ contract VRFOracle {
         oracle = _newOracle;
     }
 
+    /**
+     * @notice Update the public key used to verify ECVRF proofs
+     * @param _vrfPublicKey Compressed secp256k1 public key (33 bytes)
+     * @dev Only callable by contract owner
+     */
+    function setVrfPublicKey(bytes calldata _vrfPublicKey) external onlyOwner {
+        vrfPublicKey = _vrfPublicKey;
+    }
+
     /**
      * @notice Withdraw accumulated fees from the contract
      * @dev Only callable by contract owner
@@ -1,8 +1,5 @@
--- Drop the table if it exists
-DROP TABLE IF EXISTS zamaoracle_vrf_oracle.pending_requests;
-
 -- Create pending_requests table for durable request queue
-CREATE TABLE zamaoracle_vrf_oracle.pending_requests (
+CREATE TABLE IF NOT EXISTS zamaoracle_vrf_oracle.pending_requests (
     request_id BYTEA PRIMARY KEY,
     contract_address VARCHAR(42) NOT NULL,
     status VARCHAR(20) NOT NULL DEFAULT 'pending',
@@ -0,0 +1,4 @@
+-- Store the request data used to derive the VRF seed
+ALTER TABLE zamaoracle_vrf_oracle.pending_requests
+    ADD COLUMN IF NOT EXISTS requester VARCHAR(42),
+    ADD COLUMN IF NOT EXISTS block_hash BYTEA;
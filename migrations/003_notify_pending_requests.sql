@@ -0,0 +1,15 @@
+-- Notify listeners whenever a request becomes pending (new or requeued)
+CREATE OR REPLACE FUNCTION zamaoracle_vrf_oracle.notify_pending_request()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('zamaoracle_pending_requests', encode(NEW.request_id, 'hex'));
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+DROP TRIGGER IF EXISTS notify_pending_requests ON zamaoracle_vrf_oracle.pending_requests;
+CREATE TRIGGER notify_pending_requests
+    AFTER INSERT OR UPDATE OF status ON zamaoracle_vrf_oracle.pending_requests
+    FOR EACH ROW
+    WHEN (NEW.status = 'pending')
+    EXECUTE FUNCTION zamaoracle_vrf_oracle.notify_pending_request();
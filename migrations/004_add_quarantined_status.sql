@@ -0,0 +1,7 @@
+-- Requests whose fulfillment call reverts on its own are set aside instead of retried
+ALTER TABLE zamaoracle_vrf_oracle.pending_requests
+    DROP CONSTRAINT IF EXISTS valid_status;
+
+ALTER TABLE zamaoracle_vrf_oracle.pending_requests
+    ADD CONSTRAINT valid_status
+    CHECK (status IN ('pending', 'processing', 'fulfilled', 'failed', 'quarantined'));
@@ -0,0 +1,15 @@
+-- Relayer top-ups sent from the treasury account, also used to enforce the daily spend cap.
+-- A top-up is reserved against the cap before it is sent, and settled on its receipt.
+CREATE TABLE IF NOT EXISTS zamaoracle_vrf_oracle.relayer_topups (
+    id BIGSERIAL PRIMARY KEY,
+    treasury_address VARCHAR(42) NOT NULL,
+    relayer_address VARCHAR(42) NOT NULL,
+    amount_wei NUMERIC(78, 0) NOT NULL,
+    tx_hash BYTEA,
+    status VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CONSTRAINT valid_topup_status CHECK (status IN ('reserved', 'sent', 'confirmed', 'failed'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_relayer_topups_treasury_created_at
+    ON zamaoracle_vrf_oracle.relayer_topups(treasury_address, created_at);
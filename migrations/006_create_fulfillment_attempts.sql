@@ -0,0 +1,22 @@
+-- Every fulfillment attempt of a request, keyed by the retry count it was dequeued with
+CREATE TABLE IF NOT EXISTS zamaoracle_vrf_oracle.fulfillment_attempts (
+    request_id BYTEA NOT NULL,
+    attempt INTEGER NOT NULL,
+    relayer_address VARCHAR(42) NOT NULL,
+    tx_hash BYTEA,
+    nonce BIGINT,
+    gas_used BIGINT,
+    effective_gas_price NUMERIC(78, 0),
+    outcome VARCHAR(20) NOT NULL,
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (request_id, attempt),
+    CONSTRAINT valid_outcome CHECK (outcome IN ('sent', 'mined', 'reverted', 'failed', 'dropped'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_fulfillment_attempts_tx_hash
+    ON zamaoracle_vrf_oracle.fulfillment_attempts(tx_hash);
+CREATE INDEX IF NOT EXISTS idx_fulfillment_attempts_sent
+    ON zamaoracle_vrf_oracle.fulfillment_attempts(request_id, attempt)
+    WHERE outcome = 'sent';
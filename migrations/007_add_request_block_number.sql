@@ -0,0 +1,11 @@
+-- Block of the request, to wait for confirmations and detect reorgs
+ALTER TABLE zamaoracle_vrf_oracle.pending_requests
+    ADD COLUMN IF NOT EXISTS block_number BIGINT;
+
+-- Requests whose block was reorged out are cancelled
+ALTER TABLE zamaoracle_vrf_oracle.pending_requests
+    DROP CONSTRAINT IF EXISTS valid_status;
+
+ALTER TABLE zamaoracle_vrf_oracle.pending_requests
+    ADD CONSTRAINT valid_status
+    CHECK (status IN ('pending', 'processing', 'fulfilled', 'failed', 'quarantined', 'cancelled'));
@@ -0,0 +1,221 @@
+//! Stand-in remote signer for local runs and tests.
+//!
+//! Serves the subset of the Web3Signer `eth1` API and of the JSON-RPC signing methods
+//! that the relayer uses, backed by plain private keys:
+//!
+//! - `GET /upcheck`
+//! - `GET /api/v1/eth1/publicKeys`
+//! - `POST /api/v1/eth1/sign/{publicKey}` with `{"data": "0x..."}`
+//! - `POST /` JSON-RPC `eth_accounts` and `eth_signTransaction`
+//!
+//! SIGNER_PRIVATE_KEYS=0xkey1,0xkey2
+//! SIGNER_LISTEN=127.0.0.1:9000
+
+use alloy::{
+    eips::eip2718::Encodable2718,
+    network::{EthereumWallet, TransactionBuilder},
+    primitives::{hex, keccak256, Address, Bytes},
+    rpc::types::TransactionRequest,
+    signers::{local::PrivateKeySigner, SignerSync},
+};
+use anyhow::{anyhow, Context, Result};
+use dotenvy::dotenv;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+struct Signer {
+    keys: Vec<PrivateKeySigner>,
+    wallet: EthereumWallet,
+}
+
+impl Signer {
+    fn new(keys: Vec<PrivateKeySigner>) -> Result<Self> {
+        let mut keys = keys.into_iter();
+        let first = keys
+            .next()
+            .ok_or_else(|| anyhow!("SIGNER_PRIVATE_KEYS is empty"))?;
+
+        let mut wallet = EthereumWallet::from(first.clone());
+        let mut all = vec![first];
+        for key in keys {
+            wallet.register_signer(key.clone());
+            all.push(key);
+        }
+
+        Ok(Self { keys: all, wallet })
+    }
+
+    /// Uncompressed public key, as listed by Web3Signer
+    fn public_key(key: &PrivateKeySigner) -> String {
+        let point = key.credential().verifying_key().to_encoded_point(false);
+        hex::encode_prefixed(point.as_bytes())
+    }
+
+    fn public_keys(&self) -> Vec<String> {
+        self.keys.iter().map(Self::public_key).collect()
+    }
+
+    fn sign(&self, public_key: &str, body: &[u8]) -> Result<String> {
+        #[derive(Deserialize)]
+        struct SignRequest {
+            data: Bytes,
+        }
+
+        let key = self
+            .keys
+            .iter()
+            .find(|key| Self::public_key(key).eq_ignore_ascii_case(public_key))
+            .ok_or_else(|| anyhow!("Unknown public key {public_key}"))?;
+        let request: SignRequest = serde_json::from_slice(body)?;
+
+        let signature = key.sign_hash_sync(&keccak256(&request.data))?;
+        Ok(hex::encode_prefixed(signature.as_bytes()))
+    }
+
+    async fn rpc(&self, body: &[u8]) -> Value {
+        let request: Value = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(e) => return rpc_error(Value::Null, -32700, &e.to_string()),
+        };
+        let id = request["id"].clone();
+
+        let result = match request["method"].as_str() {
+            Some("eth_accounts") => {
+                let accounts: Vec<Address> = self.keys.iter().map(|key| key.address()).collect();
+                Ok(json!(accounts))
+            }
+            Some("eth_signTransaction") => self.sign_transaction(&request["params"][0]).await,
+            Some(method) => return rpc_error(id, -32601, &format!("Method {method} not found")),
+            None => return rpc_error(id, -32600, "Missing method"),
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => rpc_error(id, -32000, &e.to_string()),
+        }
+    }
+
+    async fn sign_transaction(&self, params: &Value) -> Result<Value> {
+        let request: TransactionRequest = serde_json::from_value(params.clone())?;
+        let from = request.from.context("Missing from")?;
+        if !self.keys.iter().any(|key| key.address() == from) {
+            return Err(anyhow!("Unknown account {from}"));
+        }
+
+        let signed = request
+            .build(&self.wallet)
+            .await
+            .map_err(|e| anyhow!("Failed to sign transaction: {e}"))?;
+        Ok(json!(hex::encode_prefixed(signed.encoded_2718())))
+    }
+}
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+async fn handle(signer: &Signer, stream: TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).await?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let (status, content_type, response) = match (method.as_str(), path.as_str()) {
+        ("GET", "/upcheck") => ("200 OK", "text/plain", "OK".to_string()),
+        ("GET", "/api/v1/eth1/publicKeys") => (
+            "200 OK",
+            "application/json",
+            json!(signer.public_keys()).to_string(),
+        ),
+        ("POST", "/") => (
+            "200 OK",
+            "application/json",
+            signer.rpc(&body).await.to_string(),
+        ),
+        ("POST", path) if path.starts_with("/api/v1/eth1/sign/") => {
+            let public_key = path.trim_start_matches("/api/v1/eth1/sign/");
+            match signer.sign(public_key, &body) {
+                Ok(signature) => ("200 OK", "text/plain", signature),
+                Err(e) => ("400 Bad Request", "text/plain", e.to_string()),
+            }
+        }
+        _ => ("404 Not Found", "text/plain", "Not Found".to_string()),
+    };
+
+    let mut stream = reader.into_inner();
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                response.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    tracing_subscriber::fmt::init();
+
+    let keys = env::var("SIGNER_PRIVATE_KEYS").context("SIGNER_PRIVATE_KEYS is not set")?;
+    let keys = keys
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| key.parse::<PrivateKeySigner>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to parse SIGNER_PRIVATE_KEYS")?;
+    let signer = Arc::new(Signer::new(keys)?);
+
+    let listen = env::var("SIGNER_LISTEN").unwrap_or_else(|_| "127.0.0.1:9000".to_string());
+    let listener = TcpListener::bind(&listen).await?;
+    info!(
+        "Signer listening on {} with {} keys",
+        listen,
+        signer.keys.len()
+    );
+    for key in signer.keys.iter() {
+        info!("  {}", key.address());
+    }
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let signer = signer.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(&signer, stream).await {
+                warn!("Request from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
@@ -37,7 +37,7 @@ pub enum Commands {
 
     /// Run the queue processor to fulfill pending randomness requests
     QueueProcessor {
-        /// Poll interval in seconds (default: 5)
+        /// Fallback poll interval in seconds when no notification arrives (default: 5)
         #[arg(long, default_value = "5")]
         poll_interval: u64,
 
@@ -45,4 +45,18 @@ pub enum Commands {
         #[arg(short, long)]
         migrate: bool,
     },
+
+    /// Verify the VRF proof behind a fulfilled request
+    Verify {
+        /// Request ID (32-byte hex)
+        request_id: String,
+
+        /// Oracle VRF public key (SEC1 hex); defaults to the key registered on the contract
+        #[arg(long)]
+        public_key: Option<String>,
+
+        /// RPC endpoint used to fetch the fulfillment transaction (default: $RPC_URL)
+        #[arg(long)]
+        rpc_url: Option<String>,
+    },
 }
@@ -159,7 +159,7 @@ impl DataLayer {
             SELECT
                 COUNT(*) FILTER (WHERE status IN ('pending', 'processing')) as pending,
                 COUNT(*) FILTER (WHERE status = 'fulfilled') as fulfilled,
-                COUNT(*) FILTER (WHERE status = 'failed') as failed
+                COUNT(*) FILTER (WHERE status IN ('failed', 'quarantined')) as failed
             FROM zamaoracle_vrf_oracle.pending_requests
         "#;
 
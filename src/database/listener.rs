@@ -0,0 +1,81 @@
+//! Wake-ups for the queue processor via Postgres `LISTEN`/`NOTIFY`.
+//!
+//! A trigger on `pending_requests` notifies `zamaoracle_pending_requests` whenever a
+//! request becomes pending. The listener holds a dedicated connection (pooled
+//! connections cannot keep a `LISTEN` registered) and reconnects if it drops.
+
+use futures::{stream, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_postgres::{AsyncMessage, NoTls};
+use tracing::{debug, info, warn};
+
+pub const PENDING_REQUESTS_CHANNEL: &str = "zamaoracle_pending_requests";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct QueueListener {
+    notify: Arc<Notify>,
+}
+
+impl QueueListener {
+    /// Start listening in the background
+    pub fn spawn(database_url: String) -> Self {
+        let notify = Arc::new(Notify::new());
+        let listener = Self {
+            notify: notify.clone(),
+        };
+
+        tokio::spawn(async move {
+            loop {
+                match listen(&database_url, &notify).await {
+                    Ok(()) => warn!("Queue listener connection closed, reconnecting..."),
+                    Err(e) => warn!("Queue listener error: {}, reconnecting...", e),
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+
+        listener
+    }
+
+    /// Wait until a request is enqueued, or `timeout` elapses
+    pub async fn wait(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.notify.notified()).await;
+    }
+}
+
+async fn listen(database_url: &str, notify: &Notify) -> Result<(), tokio_postgres::Error> {
+    let (client, mut connection) = tokio_postgres::connect(database_url, NoTls).await?;
+
+    let pump = async {
+        let mut messages = stream::poll_fn(|cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            if let AsyncMessage::Notification(notification) = message? {
+                debug!(
+                    "Received notification for request {}",
+                    notification.payload()
+                );
+                notify.notify_one();
+            }
+        }
+        Ok(())
+    };
+    tokio::pin!(pump);
+
+    // The connection must be polled for the LISTEN command to complete
+    let listen = format!("LISTEN {PENDING_REQUESTS_CHANNEL}");
+    tokio::select! {
+        result = &mut pump => return result,
+        result = client.batch_execute(&listen) => result?,
+    }
+
+    info!("Listening for new requests on {}", PENDING_REQUESTS_CHANNEL);
+
+    // Requests enqueued while disconnected were not notified
+    notify.notify_one();
+
+    pump.await
+}
@@ -0,0 +1,115 @@
+//! Ownership of signing keys across oracle replicas.
+//!
+//! Replicas sharing a database also share relayer keys, and two processes sending from
+//! one key collide on nonces. Each replica therefore takes a session-level advisory lock
+//! per key and only signs with the keys it holds. Like the queue listener, the locks live
+//! on a dedicated connection: pooled connections would release them at random. When a
+//! replica exits or its connection drops the server releases its locks, and the other
+//! replicas pick the keys up on their next attempt.
+
+use alloy::primitives::Address;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio_postgres::{Client, NoTls};
+use tracing::{info, warn};
+
+/// First key of the two-key advisory locks, namespacing them from other lock users
+const KEY_LOCK_CLASS: i32 = 0x7a6f_7263;
+
+/// How often locks held elsewhere are retried and the connection is checked
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct KeyLocks {
+    held: watch::Receiver<HashSet<Address>>,
+}
+
+impl KeyLocks {
+    /// Start competing for the locks of `addresses` in the background
+    pub fn spawn(database_url: String, addresses: Vec<Address>) -> Self {
+        let (tx, rx) = watch::channel(HashSet::new());
+
+        tokio::spawn(async move {
+            loop {
+                match hold(&database_url, &addresses, &tx).await {
+                    Ok(()) => warn!("Key lock connection closed, reconnecting..."),
+                    Err(e) => warn!("Key lock connection error: {}, reconnecting...", e),
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+
+        Self { held: rx }
+    }
+
+    /// Hold every key without coordinating, for a single replica
+    pub fn unlocked(addresses: Vec<Address>) -> Self {
+        let (_, rx) = watch::channel(addresses.into_iter().collect());
+        Self { held: rx }
+    }
+
+    /// Keys currently held by this replica
+    pub fn held(&self) -> HashSet<Address> {
+        self.held.borrow().clone()
+    }
+}
+
+/// Advisory lock key of `address`. A collision only makes one replica own both keys.
+fn lock_key(address: &Address) -> i32 {
+    let bytes = address.as_slice();
+    i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+async fn hold(
+    database_url: &str,
+    addresses: &[Address],
+    held: &watch::Sender<HashSet<Address>>,
+) -> Result<(), tokio_postgres::Error> {
+    let (client, connection) = tokio_postgres::connect(database_url, NoTls).await?;
+    let connection = tokio::spawn(connection);
+
+    let result = acquire(&client, addresses, held).await;
+
+    // The locks go with the session, stop using the keys before it closes
+    held.send_replace(HashSet::new());
+    drop(client);
+    let _ = connection.await;
+
+    result
+}
+
+async fn acquire(
+    client: &Client,
+    addresses: &[Address],
+    held: &watch::Sender<HashSet<Address>>,
+) -> Result<(), tokio_postgres::Error> {
+    let mut interval = tokio::time::interval(LOCK_RETRY_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        // Notice a dead session even when every lock is already held
+        client.batch_execute("SELECT 1").await?;
+
+        for address in addresses.iter() {
+            if held.borrow().contains(address) {
+                continue;
+            }
+
+            let row = client
+                .query_one(
+                    "SELECT pg_try_advisory_lock($1, $2)",
+                    &[&KEY_LOCK_CLASS, &lock_key(address)],
+                )
+                .await?;
+            if row.get::<_, bool>(0) {
+                info!("Acquired the lock on key {}", address);
+                held.send_modify(|held| {
+                    held.insert(*address);
+                });
+            }
+        }
+    }
+}
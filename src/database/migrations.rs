@@ -0,0 +1,161 @@
+//! Versioned schema migrations.
+//!
+//! Migrations are the numbered files in `migrations/`, embedded at build time. Each one
+//! is applied exactly once, in order, and recorded in `schema_migrations` with the
+//! SHA-256 checksum of its contents. Editing an applied migration is refused: add a new
+//! numbered file instead.
+//!
+//! Replicas starting together would race on `schema_migrations`, so a run holds a session
+//! advisory lock on its own connection from before the table is created until the last
+//! migration is committed. The pending versions are computed only once the lock is held.
+
+use sha2::{Digest, Sha256};
+use tokio_postgres::{Client, NoTls};
+use tracing::info;
+
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.sql.as_bytes()))
+    }
+}
+
+/// All migrations, ordered by version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_pending_requests",
+        sql: include_str!("../../migrations/001_create_pending_requests.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "add_request_seed_columns",
+        sql: include_str!("../../migrations/002_add_request_seed_columns.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "notify_pending_requests",
+        sql: include_str!("../../migrations/003_notify_pending_requests.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "add_quarantined_status",
+        sql: include_str!("../../migrations/004_add_quarantined_status.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "create_relayer_topups",
+        sql: include_str!("../../migrations/005_create_relayer_topups.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "create_fulfillment_attempts",
+        sql: include_str!("../../migrations/006_create_fulfillment_attempts.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "add_request_block_number",
+        sql: include_str!("../../migrations/007_add_request_block_number.sql"),
+    },
+];
+
+/// Key for the advisory lock serialising concurrent migration runs
+const MIGRATION_LOCK_KEY: i64 = 0x7a616d615f6d6967;
+
+/// Apply all pending migrations and return how many were applied
+pub async fn run(database_url: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    // Pooled connections would release a session lock at random, use a dedicated one
+    let (client, connection) = tokio_postgres::connect(database_url, NoTls).await?;
+    let connection = tokio::spawn(connection);
+
+    let result = async {
+        client
+            .execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK_KEY])
+            .await?;
+        apply_pending(&client).await
+    }
+    .await;
+
+    // Closing the session releases the lock
+    drop(client);
+    let _ = connection.await;
+
+    result
+}
+
+async fn apply_pending(client: &Client) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    client
+        .batch_execute(
+            r#"
+            CREATE SCHEMA IF NOT EXISTS zamaoracle_vrf_oracle;
+            CREATE TABLE IF NOT EXISTS zamaoracle_vrf_oracle.schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum CHAR(64) NOT NULL,
+                applied_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+            )
+            "#,
+        )
+        .await?;
+
+    let rows = client
+        .query(
+            "SELECT version, checksum FROM zamaoracle_vrf_oracle.schema_migrations ORDER BY version",
+            &[],
+        )
+        .await?;
+
+    let mut applied = Vec::with_capacity(rows.len());
+    for row in rows.iter() {
+        let version: i32 = row.get(0);
+        let checksum: String = row.get(1);
+
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == version)
+            .ok_or_else(|| format!("Database has unknown migration version {version} applied"))?;
+
+        if migration.checksum() != checksum.trim() {
+            return Err(format!(
+                "Checksum mismatch for applied migration {:03}_{}: the file was modified after it was applied",
+                migration.version, migration.name
+            )
+            .into());
+        }
+
+        applied.push(version);
+    }
+
+    let mut count = 0;
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        // A multi-statement simple query runs as a single implicit transaction, so the
+        // migration and its schema_migrations row are committed or rolled back together
+        let batch = format!(
+            "{sql}\n;\nINSERT INTO zamaoracle_vrf_oracle.schema_migrations (version, name, checksum) VALUES ({version}, '{name}', '{checksum}');",
+            sql = migration.sql,
+            version = migration.version,
+            name = migration.name,
+            checksum = migration.checksum(),
+        );
+
+        client.batch_execute(&batch).await.map_err(|e| {
+            format!(
+                "Migration {:03}_{} failed: {e}",
+                migration.version, migration.name
+            )
+        })?;
+
+        info!(
+            "Applied migration {:03}_{}",
+            migration.version, migration.name
+        );
+        count += 1;
+    }
+
+    Ok(count)
+}
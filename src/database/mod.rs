@@ -1,15 +1,185 @@
-use alloy::primitives::{Address, FixedBytes};
+mod listener;
+mod locks;
+pub mod migrations;
+
+pub use listener::QueueListener;
+pub use locks::KeyLocks;
+
+use crate::oracle::verification::FulfillmentRecord;
+use alloy::primitives::{Address, FixedBytes, U256};
+use alloy::rpc::types::TransactionReceipt;
 use rindexer::PostgresClient;
 use std::sync::Arc;
-use tracing::{error, info, trace};
+use std::time::Duration;
+use tokio_postgres::Row;
+use tracing::{error, info, trace, warn};
 
 #[derive(Debug, Clone)]
 pub struct PendingRequest {
     pub request_id: FixedBytes<32>,
     pub contract_address: Address,
+    pub requester: Address,
+    pub block_hash: FixedBytes<32>,
     pub status: String,
     pub retry_count: i32,
     pub network: String,
+    /// Time since the request was enqueued
+    pub queued_for: Duration,
+    /// Block of the request, unknown for requests enqueued before it was recorded
+    pub block_number: Option<u64>,
+}
+
+impl PendingRequest {
+    /// Parse a row returned by the dequeue queries
+    fn from_row(row: &Row) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let request_id_bytes: &[u8] = row.get(0);
+        let request_id =
+            FixedBytes::<32>::try_from(request_id_bytes).map_err(|_| "Invalid request_id bytes")?;
+
+        let contract_address_str: String = row.get(1);
+        let contract_address = contract_address_str
+            .parse::<Address>()
+            .map_err(|_| "Invalid contract address")?;
+
+        // Requests enqueued before the seed columns existed have no requester/block hash
+        let requester = match row.get::<_, Option<String>>(5) {
+            Some(requester) => requester
+                .parse::<Address>()
+                .map_err(|_| "Invalid requester address")?,
+            None => Address::ZERO,
+        };
+        let block_hash = match row.get::<_, Option<&[u8]>>(6) {
+            Some(bytes) => {
+                FixedBytes::<32>::try_from(bytes).map_err(|_| "Invalid block_hash bytes")?
+            }
+            None => FixedBytes::ZERO,
+        };
+
+        Ok(Self {
+            request_id,
+            contract_address,
+            requester,
+            block_hash,
+            status: row.get(2),
+            retry_count: row.get(3),
+            network: row.get(4),
+            queued_for: Duration::from_secs(row.get::<_, i64>(7).max(0) as u64),
+            block_number: row.get::<_, Option<i64>>(8).map(|number| number as u64),
+        })
+    }
+}
+
+/// State of a row of `relayer_topups`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopupStatus {
+    /// Counted against the daily cap, not sent yet
+    Reserved,
+    /// Broadcast, no receipt yet
+    Sent,
+    Confirmed,
+    /// Never sent or reverted, no longer counted against the cap
+    Failed,
+}
+
+impl TopupStatus {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reserved => "reserved",
+            Self::Sent => "sent",
+            Self::Confirmed => "confirmed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Outcome of a fulfillment attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    /// Broadcast, not confirmed yet
+    Sent,
+    Mined,
+    Reverted,
+    /// Never broadcast, or no receipt could be obtained
+    Failed,
+    /// Left the mempool without being mined, or its nonce was used by another transaction
+    Dropped,
+}
+
+impl AttemptOutcome {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Mined => "mined",
+            Self::Reverted => "reverted",
+            Self::Failed => "failed",
+            Self::Dropped => "dropped",
+        }
+    }
+}
+
+/// A row of `fulfillment_attempts`, shared by the requests sent together
+#[derive(Debug, Clone)]
+pub struct FulfillmentAttempt {
+    pub relayer: Address,
+    pub tx_hash: Option<FixedBytes<32>>,
+    pub nonce: Option<u64>,
+    pub gas_used: Option<u64>,
+    pub effective_gas_price: Option<u128>,
+    pub outcome: AttemptOutcome,
+    pub error: Option<String>,
+}
+
+impl FulfillmentAttempt {
+    /// Attempt that ended before anything was broadcast
+    pub fn failed(relayer: Address, error: String) -> Self {
+        Self {
+            relayer,
+            tx_hash: None,
+            nonce: None,
+            gas_used: None,
+            effective_gas_price: None,
+            outcome: AttemptOutcome::Failed,
+            error: Some(error),
+        }
+    }
+
+    /// Attempt whose transaction was mined, successfully or not
+    pub fn mined(relayer: Address, nonce: Option<u64>, receipt: &TransactionReceipt) -> Self {
+        Self {
+            relayer,
+            tx_hash: Some(receipt.transaction_hash),
+            nonce,
+            gas_used: Some(receipt.gas_used),
+            effective_gas_price: Some(receipt.effective_gas_price),
+            outcome: if receipt.status() {
+                AttemptOutcome::Mined
+            } else {
+                AttemptOutcome::Reverted
+            },
+            error: None,
+        }
+    }
+
+    /// Attempt broadcast in `tx_hash`, waiting for its receipt
+    pub const fn sent(relayer: Address, tx_hash: FixedBytes<32>, nonce: Option<u64>) -> Self {
+        Self {
+            relayer,
+            tx_hash: Some(tx_hash),
+            nonce,
+            gas_used: None,
+            effective_gas_price: None,
+            outcome: AttemptOutcome::Sent,
+            error: None,
+        }
+    }
+}
+
+/// Broadcast transaction of an unconfirmed attempt
+#[derive(Debug, Clone)]
+pub struct SentAttempt {
+    pub relayer: Address,
+    pub tx_hash: FixedBytes<32>,
+    pub nonce: Option<u64>,
 }
 
 #[derive(Clone)]
@@ -22,18 +192,32 @@ impl QueueDatabase {
         Self { client }
     }
 
-    /// Enqueue a new randomness request
+    /// Enqueue a new randomness request.
+    ///
+    /// A request seen again in another block, after a reorg, takes the new block and goes
+    /// back to pending if it was being processed or was cancelled when its previous block
+    /// was reorged out.
     pub async fn enqueue_request(
         &self,
         request_id: FixedBytes<32>,
         contract_address: Address,
+        requester: Address,
+        block_hash: FixedBytes<32>,
+        block_number: u64,
         network: &str,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let query = r#"
-            INSERT INTO zamaoracle_vrf_oracle.pending_requests
-            (request_id, contract_address, network, status)
-            VALUES ($1, $2, $3, 'pending')
-            ON CONFLICT (request_id) DO NOTHING
+            INSERT INTO zamaoracle_vrf_oracle.pending_requests AS p
+            (request_id, contract_address, requester, block_hash, block_number, network, status)
+            VALUES ($1, $2, $3, $4, $5, $6, 'pending')
+            ON CONFLICT (request_id) DO UPDATE
+            SET block_hash = EXCLUDED.block_hash,
+                block_number = EXCLUDED.block_number,
+                status = 'pending',
+                processing_started_at = NULL,
+                updated_at = NOW()
+            WHERE p.status IN ('pending', 'processing', 'cancelled')
+                AND p.block_hash IS DISTINCT FROM EXCLUDED.block_hash
         "#;
 
         self.client
@@ -42,6 +226,9 @@ impl QueueDatabase {
                 &[
                     &request_id.as_slice(),
                     &contract_address.to_string(),
+                    &requester.to_string(),
+                    &block_hash.as_slice(),
+                    &(block_number as i64),
                     &network,
                 ],
             )
@@ -56,9 +243,10 @@ impl QueueDatabase {
         Ok(())
     }
 
-    /// Dequeue a pending request for processing
+    /// Dequeue a pending request mined at or below `confirmed_block` for processing
     pub async fn dequeue_request(
         &self,
+        confirmed_block: u64,
     ) -> Result<Option<PendingRequest>, Box<dyn std::error::Error + Send + Sync>> {
         let query = r#"
             UPDATE zamaoracle_vrf_oracle.pending_requests
@@ -72,34 +260,23 @@ impl QueueDatabase {
                     OR (status = 'processing'
                         AND processing_started_at < NOW() - INTERVAL '5 minutes'))
                     AND retry_count < max_retries
+                    AND (block_number IS NULL OR block_number <= $1)
                 ORDER BY created_at
                 FOR UPDATE SKIP LOCKED
                 LIMIT 1
             )
-            RETURNING request_id, contract_address, status, retry_count, network
+            RETURNING request_id, contract_address, status, retry_count, network, requester, block_hash,
+                EXTRACT(EPOCH FROM NOW() - created_at)::BIGINT, block_number
         "#;
 
-        let rows = self.client.query(query, &[]).await?;
-
-        if let Some(row) = rows.first() {
-            let request_id_bytes: &[u8] = row.get(0);
-            let request_id = FixedBytes::<32>::try_from(request_id_bytes)
-                .map_err(|_| "Invalid request_id bytes")?;
+        let rows = self
+            .client
+            .query(query, &[&(confirmed_block as i64)])
+            .await?;
 
-            let contract_address_str: String = row.get(1);
-            let contract_address = contract_address_str
-                .parse::<Address>()
-                .map_err(|_| "Invalid contract address")?;
-
-            Ok(Some(PendingRequest {
-                request_id,
-                contract_address,
-                status: row.get(2),
-                retry_count: row.get(3),
-                network: row.get(4),
-            }))
-        } else {
-            Ok(None)
+        match rows.first() {
+            Some(row) => Ok(Some(PendingRequest::from_row(row)?)),
+            None => Ok(None),
         }
     }
 
@@ -190,10 +367,14 @@ impl QueueDatabase {
         Ok(row.get(0))
     }
 
-    /// Dequeue multiple pending requests for batch processing
+    /// Dequeue multiple pending requests for batch processing.
+    ///
+    /// Only requests mined at or below `confirmed_block` are eligible, so a request is
+    /// not fulfilled before its block has the configured confirmation depth.
     pub async fn dequeue_requests(
         &self,
         limit: usize,
+        confirmed_block: u64,
     ) -> Result<Vec<PendingRequest>, Box<dyn std::error::Error + Send + Sync>> {
         let query = r#"
             UPDATE zamaoracle_vrf_oracle.pending_requests
@@ -207,33 +388,23 @@ impl QueueDatabase {
                     OR (status = 'processing'
                         AND processing_started_at < NOW() - INTERVAL '5 minutes'))
                     AND retry_count < max_retries
+                    AND (block_number IS NULL OR block_number <= $2)
                 ORDER BY created_at
                 FOR UPDATE SKIP LOCKED
                 LIMIT $1
             )
-            RETURNING request_id, contract_address, status, retry_count, network
+            RETURNING request_id, contract_address, status, retry_count, network, requester, block_hash,
+                EXTRACT(EPOCH FROM NOW() - created_at)::BIGINT, block_number
         "#;
 
-        let rows = self.client.query(query, &[&(limit as i64)]).await?;
+        let rows = self
+            .client
+            .query(query, &[&(limit as i64), &(confirmed_block as i64)])
+            .await?;
         let mut requests = Vec::new();
 
-        for row in rows {
-            let request_id_bytes: &[u8] = row.get(0);
-            let request_id = FixedBytes::<32>::try_from(request_id_bytes)
-                .map_err(|_| "Invalid request_id bytes")?;
-
-            let contract_address_str: String = row.get(1);
-            let contract_address = contract_address_str
-                .parse::<Address>()
-                .map_err(|_| "Invalid contract address")?;
-
-            requests.push(PendingRequest {
-                request_id,
-                contract_address,
-                status: row.get(2),
-                retry_count: row.get(3),
-                network: row.get(4),
-            });
+        for row in rows.iter() {
+            requests.push(PendingRequest::from_row(row)?);
         }
 
         if !requests.is_empty() {
@@ -283,11 +454,365 @@ impl QueueDatabase {
         Ok(())
     }
 
-    /// Run the migration to create the pending_requests table
-    pub async fn run_migration(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let migration = include_str!("../../migrations/001_create_pending_requests.sql");
-        self.client.batch_execute(migration).await?;
-        info!("Successfully ran pending_requests migration");
+    /// Record the current state of the attempt each request was dequeued for
+    pub async fn record_attempts(
+        &self,
+        requests: &[PendingRequest],
+        attempt: &FulfillmentAttempt,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if requests.is_empty() {
+            return Ok(());
+        }
+
+        let query = r#"
+            INSERT INTO zamaoracle_vrf_oracle.fulfillment_attempts
+            (request_id, attempt, relayer_address, tx_hash, nonce, gas_used, effective_gas_price,
+                outcome, error)
+            SELECT request_id, attempt, $3::TEXT, $4::BYTEA, $5::BIGINT, $6::BIGINT,
+                $7::TEXT::NUMERIC, $8::TEXT, $9::TEXT
+            FROM UNNEST($1::BYTEA[], $2::INTEGER[]) AS t(request_id, attempt)
+            ON CONFLICT (request_id, attempt) DO UPDATE
+            SET relayer_address = EXCLUDED.relayer_address,
+                tx_hash = EXCLUDED.tx_hash,
+                nonce = EXCLUDED.nonce,
+                gas_used = EXCLUDED.gas_used,
+                effective_gas_price = EXCLUDED.effective_gas_price,
+                outcome = EXCLUDED.outcome,
+                error = EXCLUDED.error,
+                updated_at = NOW()
+        "#;
+
+        let request_id_bytes: Vec<Vec<u8>> = requests
+            .iter()
+            .map(|r| r.request_id.as_slice().to_vec())
+            .collect();
+        let attempts: Vec<i32> = requests.iter().map(|r| r.retry_count).collect();
+
+        self.client
+            .execute(
+                query,
+                &[
+                    &request_id_bytes,
+                    &attempts,
+                    &attempt.relayer.to_string(),
+                    &attempt.tx_hash.as_ref().map(|hash| hash.as_slice()),
+                    &attempt.nonce.map(|nonce| nonce as i64),
+                    &attempt.gas_used.map(|gas| gas as i64),
+                    &attempt.effective_gas_price.map(|price| price.to_string()),
+                    &attempt.outcome.as_str(),
+                    &attempt.error,
+                ],
+            )
+            .await?;
+
+        trace!(
+            "Recorded {} attempt of {} requests",
+            attempt.outcome.as_str(),
+            requests.len()
+        );
+
+        Ok(())
+    }
+
+    /// Processing requests whose current attempt was broadcast but not confirmed
+    pub async fn get_unconfirmed_attempts(
+        &self,
+    ) -> Result<Vec<(PendingRequest, SentAttempt)>, Box<dyn std::error::Error + Send + Sync>> {
+        let query = r#"
+            SELECT p.request_id, p.contract_address, p.status, p.retry_count, p.network,
+                p.requester, p.block_hash, EXTRACT(EPOCH FROM NOW() - p.created_at)::BIGINT,
+                p.block_number, a.relayer_address, a.tx_hash, a.nonce
+            FROM zamaoracle_vrf_oracle.pending_requests p
+            JOIN zamaoracle_vrf_oracle.fulfillment_attempts a
+                ON a.request_id = p.request_id AND a.attempt = p.retry_count
+            WHERE p.status = 'processing'
+                AND a.outcome = 'sent'
+                AND a.tx_hash IS NOT NULL
+        "#;
+
+        let rows = self.client.query(query, &[]).await?;
+        let mut attempts = Vec::with_capacity(rows.len());
+
+        for row in rows.iter() {
+            let relayer: String = row.get(9);
+            let tx_hash: &[u8] = row.get(10);
+            attempts.push((
+                PendingRequest::from_row(row)?,
+                SentAttempt {
+                    relayer: relayer.parse().map_err(|_| "Invalid relayer address")?,
+                    tx_hash: FixedBytes::<32>::try_from(tx_hash)
+                        .map_err(|_| "Invalid tx_hash bytes")?,
+                    nonce: row.get::<_, Option<i64>>(11).map(|nonce| nonce as u64),
+                },
+            ));
+        }
+
+        Ok(attempts)
+    }
+
+    /// Put processing requests that were never broadcast back in the queue.
+    ///
+    /// The attempt is not counted, and requests whose attempt is recorded as sent are
+    /// left in processing since that transaction may still be mined. Returns how many were
+    /// requeued.
+    pub async fn requeue_unsent(
+        &self,
+        request_ids: &[FixedBytes<32>],
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        if request_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let query = r#"
+            UPDATE zamaoracle_vrf_oracle.pending_requests
+            SET status = 'pending',
+                retry_count = GREATEST(retry_count - 1, 0),
+                processing_started_at = NULL,
+                updated_at = NOW()
+            WHERE request_id = ANY($1)
+                AND status = 'processing'
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM zamaoracle_vrf_oracle.fulfillment_attempts a
+                    WHERE a.request_id = pending_requests.request_id
+                        AND a.attempt = pending_requests.retry_count
+                        AND a.outcome = 'sent'
+                )
+        "#;
+
+        let request_id_bytes: Vec<Vec<u8>> = request_ids
+            .iter()
+            .map(|id| id.as_slice().to_vec())
+            .collect();
+
+        let requeued = self.client.execute(query, &[&request_id_bytes]).await?;
+
+        info!("Requeued {} unsent requests", requeued);
+
+        Ok(requeued)
+    }
+
+    /// Set requests aside so they are no longer retried
+    pub async fn quarantine_requests(
+        &self,
+        request_ids: &[FixedBytes<32>],
+        reason: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if request_ids.is_empty() {
+            return Ok(());
+        }
+
+        let query = r#"
+            UPDATE zamaoracle_vrf_oracle.pending_requests
+            SET status = 'quarantined',
+                last_error = $2,
+                processing_started_at = NULL,
+                updated_at = NOW()
+            WHERE request_id = ANY($1)
+        "#;
+
+        let request_id_bytes: Vec<Vec<u8>> = request_ids
+            .iter()
+            .map(|id| id.as_slice().to_vec())
+            .collect();
+
+        self.client
+            .execute(query, &[&request_id_bytes, &reason])
+            .await?;
+
+        warn!("Quarantined {} requests: {}", request_ids.len(), reason);
+
+        Ok(())
+    }
+
+    /// Cancel processing requests whose block was reorged out.
+    ///
+    /// The attempt is not counted. Requests indexed again in another block meanwhile no
+    /// longer match their old block hash and are left alone. Returns how many were cancelled.
+    pub async fn cancel_requests(
+        &self,
+        requests: &[PendingRequest],
+        reason: &str,
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        if requests.is_empty() {
+            return Ok(0);
+        }
+
+        let query = r#"
+            UPDATE zamaoracle_vrf_oracle.pending_requests p
+            SET status = 'cancelled',
+                retry_count = p.retry_count - 1,
+                last_error = $3,
+                processing_started_at = NULL,
+                updated_at = NOW()
+            FROM UNNEST($1::BYTEA[], $2::BYTEA[]) AS r(request_id, block_hash)
+            WHERE p.request_id = r.request_id
+                AND p.block_hash = r.block_hash
+                AND p.status = 'processing'
+        "#;
+
+        let request_id_bytes: Vec<Vec<u8>> = requests
+            .iter()
+            .map(|request| request.request_id.as_slice().to_vec())
+            .collect();
+        let block_hash_bytes: Vec<Vec<u8>> = requests
+            .iter()
+            .map(|request| request.block_hash.as_slice().to_vec())
+            .collect();
+
+        let cancelled = self
+            .client
+            .execute(query, &[&request_id_bytes, &block_hash_bytes, &reason])
+            .await?;
+
+        warn!("Cancelled {} requests: {}", cancelled, reason);
+
+        Ok(cancelled)
+    }
+
+    /// Reserve a top-up against the treasury's daily cap before it is sent, returning its id
+    pub async fn reserve_topup(
+        &self,
+        treasury: Address,
+        relayer: Address,
+        amount: U256,
+    ) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+        let query = r#"
+            INSERT INTO zamaoracle_vrf_oracle.relayer_topups
+            (treasury_address, relayer_address, amount_wei, status)
+            VALUES ($1, $2, $3::TEXT::NUMERIC, $4)
+            RETURNING id
+        "#;
+
+        let row = self
+            .client
+            .query_one(
+                query,
+                &[
+                    &treasury.to_string(),
+                    &relayer.to_string(),
+                    &amount.to_string(),
+                    &TopupStatus::Reserved.as_str(),
+                ],
+            )
+            .await?;
+
+        trace!("Reserved top-up of {} wei to {}", amount, relayer);
+
+        Ok(row.get(0))
+    }
+
+    /// Move a reserved top-up to `status`, with the hash of its transaction once sent
+    pub async fn update_topup(
+        &self,
+        id: i64,
+        status: TopupStatus,
+        tx_hash: Option<FixedBytes<32>>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let query = r#"
+            UPDATE zamaoracle_vrf_oracle.relayer_topups
+            SET status = $2, tx_hash = COALESCE($3, tx_hash)
+            WHERE id = $1
+        "#;
+
+        self.client
+            .execute(
+                query,
+                &[
+                    &id,
+                    &status.as_str(),
+                    &tx_hash.as_ref().map(|h| h.as_slice()),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Total sent or reserved by the treasury over the last 24 hours
+    pub async fn get_treasury_spent_last_day(
+        &self,
+        treasury: Address,
+    ) -> Result<U256, Box<dyn std::error::Error + Send + Sync>> {
+        let query = r#"
+            SELECT COALESCE(SUM(amount_wei), 0)::TEXT
+            FROM zamaoracle_vrf_oracle.relayer_topups
+            WHERE treasury_address = $1
+                AND status <> 'failed'
+                AND created_at > NOW() - INTERVAL '1 day'
+        "#;
+
+        let row = self
+            .client
+            .query_one(query, &[&treasury.to_string()])
+            .await?;
+        let spent: String = row.get(0);
+        Ok(U256::from_str_radix(&spent, 10).map_err(|_| "Invalid top-up total")?)
+    }
+
+    /// Load the indexed request and fulfillment events for a request
+    pub async fn get_fulfillment_record(
+        &self,
+        request_id: FixedBytes<32>,
+    ) -> Result<Option<FulfillmentRecord>, Box<dyn std::error::Error + Send + Sync>> {
+        let query = r#"
+            SELECT
+                TRIM(r.contract_address::TEXT),
+                TRIM(r.requester::TEXT),
+                TRIM(r.block_hash::TEXT),
+                TRIM(f.randomness::TEXT),
+                TRIM(f.tx_hash::TEXT)
+            FROM zamaoracle_vrf_oracle.randomness_requested r
+            JOIN zamaoracle_vrf_oracle.randomness_fulfilled f
+                ON f.request_id = r.request_id
+            WHERE r.request_id = $1
+            ORDER BY r.block_number DESC
+            LIMIT 1
+        "#;
+
+        let rows = self.client.query(query, &[&request_id.as_slice()]).await?;
+
+        let Some(row) = rows.first() else {
+            return Ok(None);
+        };
+
+        let contract_address: String = row.get(0);
+        let requester: String = row.get(1);
+        let block_hash: String = row.get(2);
+        let randomness: String = row.get(3);
+        let tx_hash: String = row.get(4);
+
+        Ok(Some(FulfillmentRecord {
+            request_id,
+            contract_address: contract_address
+                .parse()
+                .map_err(|_| "Invalid contract address")?,
+            requester: requester.parse().map_err(|_| "Invalid requester address")?,
+            request_block_hash: parse_indexed_hash(&block_hash)?,
+            randomness: U256::from_str_radix(&randomness, 10).map_err(|_| "Invalid randomness")?,
+            fulfillment_tx_hash: parse_indexed_hash(&tx_hash)?,
+        }))
+    }
+
+    /// Apply any pending schema migrations
+    pub async fn run_migrations(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let database_url = std::env::var("DATABASE_URL")
+            .map_err(|_| "DATABASE_URL environment variable is not set")?;
+        let applied = migrations::run(&database_url).await?;
+        info!(
+            "Database schema up to date ({} migrations applied)",
+            applied
+        );
         Ok(())
     }
 }
+
+/// Parse a 32-byte hash stored by the indexer, either as `0x` text or as BYTEA text output
+fn parse_indexed_hash(
+    value: &str,
+) -> Result<FixedBytes<32>, Box<dyn std::error::Error + Send + Sync>> {
+    let hex_str = value.trim_start_matches("\\x").trim_start_matches("0x");
+    let bytes = hex::decode(hex_str).map_err(|_| format!("Invalid hash: {value}"))?;
+    Ok(FixedBytes::<32>::try_from(bytes.as_slice())
+        .map_err(|_| format!("Invalid hash: {value}"))?)
+}
@@ -4,3 +4,4 @@ pub mod oracle;
 pub mod queue_processor;
 pub mod relayer;
 pub mod rindexer_lib;
+pub mod shutdown;
@@ -1,3 +1,5 @@
+use alloy::primitives::FixedBytes;
+use alloy::providers::ProviderBuilder;
 use clap::Parser;
 use std::env;
 
@@ -15,8 +17,11 @@ mod oracle;
 mod queue_processor;
 mod relayer;
 mod rindexer_lib;
+mod shutdown;
 
 use cli::{Cli, Commands};
+use oracle::vrf::VrfPublicKey;
+use shutdown::Shutdown;
 
 #[tokio::main]
 async fn main() {
@@ -63,12 +68,73 @@ async fn main() {
                 info!("Migrations completed successfully");
             }
 
-            // Start processing queue
-            if let Err(e) = processor.start().await {
+            // Start processing queue until SIGINT / SIGTERM
+            if let Err(e) = processor.start(Shutdown::listen()).await {
                 eprintln!("Queue processor error: {e:?}");
                 std::process::exit(1);
             }
         }
+        Some(Commands::Verify {
+            request_id,
+            public_key,
+            rpc_url,
+        }) => {
+            if env::var("DATABASE_URL").is_err() {
+                eprintln!("Error: DATABASE_URL environment variable must be set");
+                std::process::exit(1);
+            }
+
+            let request_id = match request_id.parse::<FixedBytes<32>>() {
+                Ok(request_id) => request_id,
+                Err(e) => {
+                    eprintln!("Invalid request ID: {e}");
+                    std::process::exit(1);
+                }
+            };
+
+            let public_key = match public_key.as_deref().map(VrfPublicKey::from_hex) {
+                None => None,
+                Some(Ok(public_key)) => Some(public_key),
+                Some(Err(e)) => {
+                    eprintln!("Invalid public key: {e}");
+                    std::process::exit(1);
+                }
+            };
+
+            let rpc_url = rpc_url.clone().unwrap_or_else(|| {
+                env::var("RPC_URL").unwrap_or_else(|_| "http://127.0.0.1:8545".to_string())
+            });
+            let provider = match rpc_url.parse() {
+                Ok(url) => ProviderBuilder::new().connect_http(url),
+                Err(e) => {
+                    eprintln!("Invalid RPC URL: {e}");
+                    std::process::exit(1);
+                }
+            };
+
+            let queue_db = match queue_processor::create_postgres_client().await {
+                Ok(client) => database::QueueDatabase::new(client),
+                Err(e) => {
+                    eprintln!("Failed to connect to database: {e:?}");
+                    std::process::exit(1);
+                }
+            };
+
+            match oracle::verification::verify_request(&queue_db, &provider, request_id, public_key)
+                .await
+            {
+                Ok(report) => {
+                    println!("{report}");
+                    if !report.is_valid() {
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Verification failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
         _ => {
             // Handle other commands (indexer, graphql, run)
             let (enable_graphql, enable_indexer, port, enable_queue_processor, enable_metrics) =
@@ -106,18 +172,22 @@ async fn main() {
                 });
             }
 
+            let shutdown = Shutdown::listen();
+
             // Spawn queue processor if enabled
+            let mut queue_processor = None;
             if enable_queue_processor {
                 // Check if DATABASE_URL is set
                 if env::var("DATABASE_URL").is_ok() {
-                    tokio::spawn(async {
+                    let shutdown = shutdown.clone();
+                    queue_processor = Some(tokio::spawn(async move {
                         info!("Starting Queue Processor in background");
 
                         // Create PostgreSQL client
                         match queue_processor::create_postgres_client().await {
                             Ok(postgres_client) => {
                                 let mut processor =
-                                    queue_processor::QueueProcessor::new(postgres_client, 100); // Default 100ms poll interval
+                                    queue_processor::QueueProcessor::new(postgres_client, 5000); // Fallback poll, new requests are notified
 
                                 // Run migrations
                                 if let Err(e) = processor.run_migrations().await {
@@ -126,7 +196,7 @@ async fn main() {
                                 }
 
                                 // Start processing
-                                if let Err(e) = processor.start().await {
+                                if let Err(e) = processor.start(shutdown).await {
                                     error!("Queue processor error: {:?}", e);
                                 }
                             }
@@ -137,7 +207,7 @@ async fn main() {
                                 );
                             }
                         }
-                    });
+                    }));
                 } else {
                     warn!("DATABASE_URL not set, queue processor will not start. Set DATABASE_URL to enable queue processing.");
                 }
@@ -147,7 +217,7 @@ async fn main() {
             match path {
                 Ok(path) => {
                     let manifest_path = path.join("rindexer.yaml");
-                    let result = start_rindexer(StartDetails {
+                    let rindexer = start_rindexer(StartDetails {
                         manifest_path: &manifest_path,
                         indexing_details: if enable_indexer {
                             Some(IndexingDetails {
@@ -161,20 +231,28 @@ async fn main() {
                             enabled: enable_graphql,
                             override_port: port,
                         },
-                    })
-                    .await;
+                    });
 
-                    match result {
-                        Ok(_) => {}
-                        Err(e) => {
-                            println!("Error starting rindexer: {e:?}");
+                    tokio::select! {
+                        result = rindexer => {
+                            if let Err(e) = result {
+                                println!("Error starting rindexer: {e:?}");
+                            }
                         }
+                        () = shutdown.wait() => {}
                     }
                 }
                 Err(e) => {
                     println!("Error getting current directory: {e:?}");
                 }
             }
+
+            // Let the queue processor drain its in-flight batches before exiting
+            if let Some(queue_processor) = queue_processor {
+                if shutdown.is_triggered() {
+                    let _ = queue_processor.await;
+                }
+            }
         }
     }
 }
@@ -0,0 +1,135 @@
+//! HMAC-SHA256 DRBG (NIST SP 800-90A section 10.1.2), without reseeding.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub struct HmacDrbg {
+    k: [u8; 32],
+    v: [u8; 32],
+}
+
+impl HmacDrbg {
+    /// Instantiate the generator from its seed material
+    pub fn new(entropy: &[u8], nonce: &[u8], personalization: &[u8]) -> Self {
+        let mut drbg = Self {
+            k: [0x00; 32],
+            v: [0x01; 32],
+        };
+        drbg.update(&[entropy, nonce, personalization]);
+        drbg
+    }
+
+    /// Fill `out` with generator output
+    pub fn fill_bytes(&mut self, out: &mut [u8]) {
+        for chunk in out.chunks_mut(32) {
+            self.v = hmac_sha256(&self.k, &[&self.v]);
+            chunk.copy_from_slice(&self.v[..chunk.len()]);
+        }
+        self.update(&[]);
+    }
+
+    fn update(&mut self, data: &[&[u8]]) {
+        let mut parts: Vec<&[u8]> = vec![&self.v, &[0x00]];
+        parts.extend_from_slice(data);
+        self.k = hmac_sha256(&self.k, &parts);
+        self.v = hmac_sha256(&self.k, &[&self.v]);
+
+        if data.iter().all(|part| part.is_empty()) {
+            return;
+        }
+
+        let mut parts: Vec<&[u8]> = vec![&self.v, &[0x01]];
+        parts.extend_from_slice(data);
+        self.k = hmac_sha256(&self.k, &parts);
+        self.v = hmac_sha256(&self.k, &[&self.v]);
+    }
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Digest;
+
+    /// Secret key of the RFC 6979 appendix A.2.5 (P-256, SHA-256) examples
+    const SECRET_KEY: &str = "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+
+    /// RFC 6979 section 3.2 seeds the generator with the key and the message hash, its
+    /// first output is the signing nonce k when it is below the group order
+    fn nonce_generator(message: &[u8]) -> HmacDrbg {
+        let key = hex::decode(SECRET_KEY).unwrap();
+        HmacDrbg::new(&key, &Sha256::digest(message), &[])
+    }
+
+    fn generate(drbg: &mut HmacDrbg, len: usize) -> String {
+        let mut out = vec![0; len];
+        drbg.fill_bytes(&mut out);
+        hex::encode(out)
+    }
+
+    #[test]
+    fn rfc6979_nonces() {
+        let mut drbg = nonce_generator(b"sample");
+        assert_eq!(
+            generate(&mut drbg, 32),
+            "a6e3c57dd01abe90086538398355dd4c3b17aa873382b0f24d6129493d8aad60"
+        );
+
+        let mut drbg = nonce_generator(b"test");
+        assert_eq!(
+            generate(&mut drbg, 32),
+            "d16b6ae827f17175e040871a1c7ec3500192c4c92677336ec2537acaee0008e0"
+        );
+    }
+
+    #[test]
+    fn updates_state_between_requests() {
+        // Step 3.2.h.3: a rejected candidate updates K and V before the next one
+        let mut drbg = nonce_generator(b"sample");
+        generate(&mut drbg, 32);
+        assert_eq!(
+            generate(&mut drbg, 32),
+            "8e83dc490bc5fc4d5992bd63cd87f254adffcb930f8a8011702a88870f638fdb"
+        );
+
+        let mut drbg = nonce_generator(b"test");
+        generate(&mut drbg, 32);
+        assert_eq!(
+            generate(&mut drbg, 32),
+            "ed6fc87dcb558274e84d7d3799f12f8f279c07fa5301a7cd33f0ad9866cd8ca0"
+        );
+    }
+
+    #[test]
+    fn chains_blocks_within_a_request() {
+        let mut drbg = nonce_generator(b"sample");
+        assert_eq!(
+            generate(&mut drbg, 64),
+            "a6e3c57dd01abe90086538398355dd4c3b17aa873382b0f24d6129493d8aad60\
+             4ea44075f202fb7840f20d0cb37b780c2188791b44a6a4a8bcdaaeb5dd898873"
+        );
+
+        let mut drbg = nonce_generator(b"sample");
+        assert_eq!(
+            generate(&mut drbg, 5),
+            "a6e3c57dd0",
+            "partial blocks are truncated"
+        );
+    }
+
+    #[test]
+    fn personalization_changes_output() {
+        let key = hex::decode(SECRET_KEY).unwrap();
+        let nonce = Sha256::digest(b"sample");
+        let mut plain = HmacDrbg::new(&key, &nonce, &[]);
+        let mut personalized = HmacDrbg::new(&key, &nonce, b"zama");
+        assert_ne!(generate(&mut plain, 32), generate(&mut personalized, 32));
+    }
+}
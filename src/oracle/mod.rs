@@ -1,18 +1,34 @@
+mod hmac_drbg;
+pub mod source;
+pub mod verification;
+pub mod vrf;
+
 use crate::database::PendingRequest;
+use alloy::rpc::types::Log;
 use alloy::sol_types::SolValue;
 use alloy::{
-    primitives::{Bytes, FixedBytes, U256},
+    primitives::{keccak256, Address, Bytes, FixedBytes, U256},
     sol,
-    sol_types::SolCall,
+    sol_types::{SolCall, SolInterface},
 };
-use rand::{rngs::OsRng, RngCore};
+use source::{RandomnessError, RandomnessSource};
+use std::collections::HashSet;
 use tracing::trace;
 
 // Define the contract interface using sol! macro
 sol! {
     interface IVRFOracle {
         function fulfillRandomness(bytes32 requestId, uint256 randomness) external;
+        function fulfillRandomnessWithProof(bytes32 requestId, uint256 randomness, bytes calldata proof) external;
         function getRandomness(bytes32 requestId) external view returns (bool fulfilled, uint256 randomness);
+        function vrfPublicKey() external view returns (bytes memory);
+
+        event RandomnessFulfilled(bytes32 indexed requestId, uint256 randomness);
+        event RandomnessProof(bytes32 indexed requestId, bytes proof);
+
+        error OnlyOracle();
+        error RequestNotFound();
+        error AlreadyFulfilled();
     }
 }
 
@@ -22,6 +38,21 @@ sol! {
         function execute(bytes32 mode, bytes calldata executionData) external payable;
     }
 
+    interface IMulticall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        struct Result {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (Result[] memory returnData);
+    }
+
     struct Call {
         address to; // Replaced as `address(this)` if `address(0)`. Renamed to `to` for Ithaca Porto.
         uint256 value; // Amount of native currency (i.e. Ether) to send.
@@ -29,36 +60,86 @@ sol! {
     }
 }
 
-/// Generates a cryptographically secure random value
-pub fn generate_random_value() -> U256 {
-    let mut bytes = [0u8; 32];
-    OsRng.fill_bytes(&mut bytes);
-    U256::from_be_bytes(bytes)
+/// Why a fulfillment call reverts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallFailure {
+    AlreadyFulfilled,
+    RequestNotFound,
+    OnlyOracle,
+    Unknown(Bytes),
+}
+
+impl std::fmt::Display for CallFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyFulfilled => write!(f, "AlreadyFulfilled"),
+            Self::RequestNotFound => write!(f, "RequestNotFound"),
+            Self::OnlyOracle => write!(f, "OnlyOracle"),
+            Self::Unknown(data) if data.is_empty() => write!(f, "reverted without data"),
+            Self::Unknown(data) => write!(f, "reverted with {data}"),
+        }
+    }
+}
+
+/// Decode the revert data of a fulfillment call
+pub fn decode_revert(data: &[u8]) -> CallFailure {
+    match IVRFOracle::IVRFOracleErrors::abi_decode(data) {
+        Ok(IVRFOracle::IVRFOracleErrors::AlreadyFulfilled(_)) => CallFailure::AlreadyFulfilled,
+        Ok(IVRFOracle::IVRFOracleErrors::RequestNotFound(_)) => CallFailure::RequestNotFound,
+        Ok(IVRFOracle::IVRFOracleErrors::OnlyOracle(_)) => CallFailure::OnlyOracle,
+        Err(_) => CallFailure::Unknown(Bytes::copy_from_slice(data)),
+    }
+}
+
+/// Derives the VRF input for a request: keccak256(requestId || requester || blockHash)
+pub fn request_seed(
+    request_id: FixedBytes<32>,
+    requester: Address,
+    block_hash: FixedBytes<32>,
+) -> FixedBytes<32> {
+    let mut preimage = Vec::with_capacity(32 + 20 + 32);
+    preimage.extend_from_slice(request_id.as_slice());
+    preimage.extend_from_slice(requester.as_slice());
+    preimage.extend_from_slice(block_hash.as_slice());
+    keccak256(preimage)
 }
 
 /// Builds batch calls for multiple pending requests
 /// Returns a vector of Call structs ready for ERC7821 batch execution
-pub fn build_batch_calls(requests: &[PendingRequest]) -> Vec<Call> {
+pub fn build_batch_calls(
+    requests: &[PendingRequest],
+    source: &dyn RandomnessSource,
+) -> Result<Vec<Call>, RandomnessError> {
     requests
         .iter()
         .map(|request| {
-            let random_value = generate_random_value();
+            let output = source.generate(request)?;
             trace!(
-                "Generated random value {} for request {}",
-                random_value,
+                "Generated {} randomness {} for request {}",
+                source.name(),
+                output.value,
                 hex::encode(request.request_id)
             );
 
-            let call_data = IVRFOracle::fulfillRandomnessCall {
-                requestId: request.request_id,
-                randomness: random_value,
+            let call_data = match output.proof {
+                Some(proof) => IVRFOracle::fulfillRandomnessWithProofCall {
+                    requestId: request.request_id,
+                    randomness: output.value,
+                    proof,
+                }
+                .abi_encode(),
+                None => IVRFOracle::fulfillRandomnessCall {
+                    requestId: request.request_id,
+                    randomness: output.value,
+                }
+                .abi_encode(),
             };
 
-            Call {
+            Ok(Call {
                 to: request.contract_address,
                 value: U256::ZERO,
-                data: Bytes::from(call_data.abi_encode()),
-            }
+                data: Bytes::from(call_data),
+            })
         })
         .collect()
 }
@@ -81,9 +162,38 @@ pub fn encode_batch_for_erc7821(calls: &[Call]) -> IBEBE::executeCall {
     }
 }
 
+/// Encodes batch calls for a Multicall3 `aggregate3`.
+///
+/// A failing call reverts the whole batch, as in the other modes, so the pre-flight
+/// simulation catches it and bisection isolates the request.
+pub fn encode_batch_for_multicall3(calls: &[Call]) -> IMulticall3::aggregate3Call {
+    IMulticall3::aggregate3Call {
+        calls: calls
+            .iter()
+            .map(|call| IMulticall3::Call3 {
+                target: call.to,
+                allowFailure: false,
+                callData: call.data.clone(),
+            })
+            .collect(),
+    }
+}
+
 pub fn encode_get_randomness_call(request_id: FixedBytes<32>) -> IVRFOracle::getRandomnessCall {
     let result = IVRFOracle::getRandomnessCall {
         requestId: request_id,
     };
     result
 }
+
+/// Extracts the (contract, request ID) pairs fulfilled by a transaction from its
+/// `RandomnessFulfilled` logs
+pub fn fulfilled_request_ids(logs: &[Log]) -> HashSet<(Address, FixedBytes<32>)> {
+    logs.iter()
+        .filter_map(|log| {
+            log.log_decode::<IVRFOracle::RandomnessFulfilled>()
+                .ok()
+                .map(|decoded| (decoded.address(), decoded.inner.data.requestId))
+        })
+        .collect()
+}
@@ -0,0 +1,200 @@
+//! Entropy strategies used to fulfill randomness requests.
+
+use super::hmac_drbg::HmacDrbg;
+use super::request_seed;
+use super::vrf::{VrfError, VrfSecretKey};
+use crate::database::PendingRequest;
+use crate::relayer::RandomnessSourceType;
+use alloy::primitives::{Bytes, U256};
+use rand::{rngs::OsRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Randomness for a single request, with the VRF proof when the source produces one
+#[derive(Debug, Clone)]
+pub struct RandomnessOutput {
+    pub value: U256,
+    pub proof: Option<Bytes>,
+}
+
+#[derive(Debug)]
+pub enum RandomnessError {
+    Vrf(VrfError),
+    Unavailable(String),
+}
+
+impl std::fmt::Display for RandomnessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Vrf(e) => write!(f, "{e}"),
+            Self::Unavailable(e) => write!(f, "entropy source unavailable: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RandomnessError {}
+
+impl From<VrfError> for RandomnessError {
+    fn from(e: VrfError) -> Self {
+        Self::Vrf(e)
+    }
+}
+
+pub trait RandomnessSource: Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Produce the randomness for `request`
+    fn generate(&self, request: &PendingRequest) -> Result<RandomnessOutput, RandomnessError>;
+}
+
+/// ECVRF output over the request seed, published with its proof
+pub struct VrfRandomness {
+    key: VrfSecretKey,
+}
+
+impl VrfRandomness {
+    pub const fn new(key: VrfSecretKey) -> Self {
+        Self { key }
+    }
+}
+
+impl RandomnessSource for VrfRandomness {
+    fn name(&self) -> &'static str {
+        "vrf"
+    }
+
+    fn generate(&self, request: &PendingRequest) -> Result<RandomnessOutput, RandomnessError> {
+        let seed = request_seed(request.request_id, request.requester, request.block_hash);
+        let proof = self.key.prove(seed.as_slice())?;
+
+        Ok(RandomnessOutput {
+            value: U256::from_be_bytes(proof.to_hash()),
+            proof: Some(Bytes::copy_from_slice(&proof.to_bytes())),
+        })
+    }
+}
+
+/// Operating system randomness
+pub struct OsRandomness;
+
+impl RandomnessSource for OsRandomness {
+    fn name(&self) -> &'static str {
+        "os"
+    }
+
+    fn generate(&self, _request: &PendingRequest) -> Result<RandomnessOutput, RandomnessError> {
+        let mut bytes = [0u8; 32];
+        OsRng
+            .try_fill_bytes(&mut bytes)
+            .map_err(|e| RandomnessError::Unavailable(e.to_string()))?;
+
+        Ok(RandomnessOutput {
+            value: U256::from_be_bytes(bytes),
+            proof: None,
+        })
+    }
+}
+
+/// Seeded ChaCha20 stream, for reproducible test runs
+pub struct ChaChaRandomness {
+    rng: Mutex<ChaCha20Rng>,
+}
+
+impl ChaChaRandomness {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(ChaCha20Rng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RandomnessSource for ChaChaRandomness {
+    fn name(&self) -> &'static str {
+        "chacha"
+    }
+
+    fn generate(&self, _request: &PendingRequest) -> Result<RandomnessOutput, RandomnessError> {
+        let mut bytes = [0u8; 32];
+        self.rng
+            .lock()
+            .map_err(|_| RandomnessError::Unavailable("ChaCha RNG lock poisoned".into()))?
+            .fill_bytes(&mut bytes);
+
+        Ok(RandomnessOutput {
+            value: U256::from_be_bytes(bytes),
+            proof: None,
+        })
+    }
+}
+
+/// HMAC-DRBG keyed with an oracle secret and seeded with the request data
+pub struct HmacDrbgRandomness {
+    key: Vec<u8>,
+}
+
+impl HmacDrbgRandomness {
+    pub const fn new(key: Vec<u8>) -> Self {
+        Self { key }
+    }
+}
+
+impl RandomnessSource for HmacDrbgRandomness {
+    fn name(&self) -> &'static str {
+        "hmac_drbg"
+    }
+
+    fn generate(&self, request: &PendingRequest) -> Result<RandomnessOutput, RandomnessError> {
+        let seed = request_seed(request.request_id, request.requester, request.block_hash);
+        let mut drbg = HmacDrbg::new(
+            &self.key,
+            seed.as_slice(),
+            request.contract_address.as_slice(),
+        );
+
+        let mut bytes = [0u8; 32];
+        drbg.fill_bytes(&mut bytes);
+
+        Ok(RandomnessOutput {
+            value: U256::from_be_bytes(bytes),
+            proof: None,
+        })
+    }
+}
+
+/// Build the configured randomness source, reading its secret material from the environment:
+/// `VRF_PRIVATE_KEY` (vrf), `RANDOMNESS_SEED` (chacha) or `RANDOMNESS_HMAC_KEY` (hmac_drbg)
+pub fn from_config(
+    source_type: &RandomnessSourceType,
+) -> Result<Arc<dyn RandomnessSource>, Box<dyn std::error::Error + Send + Sync>> {
+    let source: Arc<dyn RandomnessSource> = match source_type {
+        RandomnessSourceType::Vrf => {
+            let secret = std::env::var("VRF_PRIVATE_KEY")
+                .map_err(|_| "VRF_PRIVATE_KEY environment variable is not set")?;
+            let key = VrfSecretKey::from_hex(&secret)?;
+            info!(
+                "Loaded VRF key with public key 0x{}",
+                hex::encode(key.public_key().to_bytes())
+            );
+            Arc::new(VrfRandomness::new(key))
+        }
+        RandomnessSourceType::Os => Arc::new(OsRandomness),
+        RandomnessSourceType::ChaCha => {
+            let seed = std::env::var("RANDOMNESS_SEED")
+                .map_err(|_| "RANDOMNESS_SEED environment variable is not set")?
+                .parse::<u64>()
+                .map_err(|_| "Invalid RANDOMNESS_SEED value")?;
+            Arc::new(ChaChaRandomness::new(seed))
+        }
+        RandomnessSourceType::HmacDrbg => {
+            let key = std::env::var("RANDOMNESS_HMAC_KEY")
+                .map_err(|_| "RANDOMNESS_HMAC_KEY environment variable is not set")?;
+            let key = hex::decode(key.trim().trim_start_matches("0x"))
+                .map_err(|_| "Invalid RANDOMNESS_HMAC_KEY value")?;
+            Arc::new(HmacDrbgRandomness::new(key))
+        }
+    };
+
+    Ok(source)
+}
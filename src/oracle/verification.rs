@@ -0,0 +1,171 @@
+//! Off-chain verification of published randomness against the oracle's VRF public key.
+
+use super::vrf::{VrfProof, VrfPublicKey};
+use super::{request_seed, IVRFOracle};
+use alloy::{
+    primitives::{Address, Bytes, FixedBytes, U256},
+    providers::Provider,
+    rpc::types::{Log, TransactionRequest},
+    sol_types::SolCall,
+};
+
+/// Indexed data for a fulfilled request, as written by the indexer
+#[derive(Debug, Clone)]
+pub struct FulfillmentRecord {
+    pub request_id: FixedBytes<32>,
+    pub contract_address: Address,
+    pub requester: Address,
+    pub request_block_hash: FixedBytes<32>,
+    pub randomness: U256,
+    pub fulfillment_tx_hash: FixedBytes<32>,
+}
+
+/// Outcome of verifying a single fulfillment
+#[derive(Debug, Clone)]
+pub struct VerificationReport {
+    pub request_id: FixedBytes<32>,
+    pub seed: FixedBytes<32>,
+    pub published_randomness: U256,
+    /// VRF output recomputed from the proof, if the proof verified
+    pub vrf_output: Option<U256>,
+    pub error: Option<String>,
+}
+
+impl VerificationReport {
+    /// Whether the proof verified and its output is the published randomness
+    pub fn is_valid(&self) -> bool {
+        self.vrf_output == Some(self.published_randomness)
+    }
+}
+
+impl std::fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Request:    0x{}", hex::encode(self.request_id))?;
+        writeln!(f, "Seed:       {}", self.seed)?;
+        writeln!(f, "Published:  {}", self.published_randomness)?;
+        match self.vrf_output {
+            Some(output) => writeln!(f, "VRF output: {output}")?,
+            None => writeln!(f, "VRF output: -")?,
+        }
+        if let Some(error) = &self.error {
+            writeln!(f, "Error:      {error}")?;
+        }
+        write!(
+            f,
+            "Result:     {}",
+            if self.is_valid() { "VALID" } else { "INVALID" }
+        )
+    }
+}
+
+/// Check a published fulfillment against its proof and the oracle public key
+pub fn verify_fulfillment(
+    public_key: &VrfPublicKey,
+    record: &FulfillmentRecord,
+    proof: &[u8],
+) -> VerificationReport {
+    let seed = request_seed(
+        record.request_id,
+        record.requester,
+        record.request_block_hash,
+    );
+
+    let mut report = VerificationReport {
+        request_id: record.request_id,
+        seed,
+        published_randomness: record.randomness,
+        vrf_output: None,
+        error: None,
+    };
+
+    let result =
+        VrfProof::from_bytes(proof).and_then(|proof| public_key.verify(seed.as_slice(), &proof));
+    match result {
+        Ok(output) => {
+            let output = U256::from_be_bytes(output);
+            if output != record.randomness {
+                report.error = Some("published randomness does not match the VRF output".into());
+            }
+            report.vrf_output = Some(output);
+        }
+        Err(e) => report.error = Some(e.to_string()),
+    }
+
+    report
+}
+
+/// Find the proof published for a request in a transaction's `RandomnessProof` logs
+pub fn find_proof(
+    logs: &[Log],
+    contract_address: Address,
+    request_id: FixedBytes<32>,
+) -> Option<Bytes> {
+    logs.iter()
+        .filter(|log| log.address() == contract_address)
+        .filter_map(|log| log.log_decode::<IVRFOracle::RandomnessProof>().ok())
+        .find(|decoded| decoded.inner.data.requestId == request_id)
+        .map(|decoded| decoded.inner.data.proof.clone())
+}
+
+/// Read the VRF public key registered on the oracle contract
+pub async fn fetch_public_key<P: Provider>(
+    provider: &P,
+    contract_address: Address,
+) -> Result<VrfPublicKey, Box<dyn std::error::Error + Send + Sync>> {
+    let tx = TransactionRequest::default()
+        .to(contract_address)
+        .input(IVRFOracle::vrfPublicKeyCall {}.abi_encode().into());
+    let result = provider.call(tx).await?;
+    let encoded = IVRFOracle::vrfPublicKeyCall::abi_decode_returns(&result)?;
+
+    if encoded.is_empty() {
+        return Err("Oracle contract has no VRF public key registered".into());
+    }
+
+    Ok(VrfPublicKey::from_bytes(&encoded)?)
+}
+
+/// Load a fulfillment and its on-chain proof, then verify it.
+///
+/// Uses the public key registered on the contract unless one is supplied.
+pub async fn verify_request<P: Provider>(
+    queue_db: &crate::database::QueueDatabase,
+    provider: &P,
+    request_id: FixedBytes<32>,
+    public_key: Option<VrfPublicKey>,
+) -> Result<VerificationReport, Box<dyn std::error::Error + Send + Sync>> {
+    let record = queue_db
+        .get_fulfillment_record(request_id)
+        .await?
+        .ok_or_else(|| {
+            format!(
+                "No indexed request/fulfillment found for 0x{}",
+                hex::encode(request_id)
+            )
+        })?;
+
+    let public_key = match public_key {
+        Some(public_key) => public_key,
+        None => fetch_public_key(provider, record.contract_address).await?,
+    };
+
+    let receipt = provider
+        .get_transaction_receipt(record.fulfillment_tx_hash)
+        .await?
+        .ok_or_else(|| {
+            format!(
+                "Fulfillment transaction {} not found",
+                record.fulfillment_tx_hash
+            )
+        })?;
+
+    let proof =
+        find_proof(receipt.logs(), record.contract_address, request_id).ok_or_else(|| {
+            format!(
+                "No RandomnessProof log for the request in transaction {}",
+                record.fulfillment_tx_hash
+            )
+        })?;
+
+    Ok(verify_fulfillment(&public_key, &record, &proof))
+}
@@ -0,0 +1,441 @@
+//! ECVRF over secp256k1 with SHA-256 and try-and-increment hashing to the curve
+//! (ECVRF-SECP256K1-SHA256-TAI).
+//!
+//! The construction follows RFC 9381 section 5 with the P-256 suite parameters carried
+//! over to secp256k1: compressed SEC1 point encoding, RFC 6979 nonces, a 16-byte
+//! challenge and a cofactor of 1. Proofs are `gamma (33) || c (16) || s (32)`.
+
+use super::hmac_drbg::HmacDrbg;
+use k256::elliptic_curve::{
+    ops::Reduce,
+    sec1::{FromEncodedPoint, ToEncodedPoint},
+    PrimeField,
+};
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar, SecretKey, U256};
+use sha2::{Digest, Sha256};
+
+/// Suite identifier for ECVRF-SECP256K1-SHA256-TAI
+const SUITE_STRING: u8 = 0xfe;
+
+/// Length of the challenge in bytes
+const C_LEN: usize = 16;
+
+/// Length of a compressed SEC1 point
+const POINT_LEN: usize = 33;
+
+/// Length of an encoded proof
+pub const PROOF_LEN: usize = POINT_LEN + C_LEN + 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VrfError {
+    InvalidSecretKey,
+    InvalidPublicKey,
+    InvalidProof,
+    EncodeToCurveFailed,
+    VerificationFailed,
+}
+
+impl std::fmt::Display for VrfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidSecretKey => write!(f, "invalid VRF secret key"),
+            Self::InvalidPublicKey => write!(f, "invalid VRF public key"),
+            Self::InvalidProof => write!(f, "malformed VRF proof"),
+            Self::EncodeToCurveFailed => write!(f, "failed to hash VRF input to the curve"),
+            Self::VerificationFailed => write!(f, "VRF proof does not verify"),
+        }
+    }
+}
+
+impl std::error::Error for VrfError {}
+
+/// The oracle's VRF signing key
+#[derive(Clone)]
+pub struct VrfSecretKey {
+    secret: Scalar,
+    public: VrfPublicKey,
+}
+
+impl VrfSecretKey {
+    /// Parse a 32-byte secret key, with or without a `0x` prefix
+    pub fn from_hex(value: &str) -> Result<Self, VrfError> {
+        let bytes = hex::decode(value.trim().trim_start_matches("0x"))
+            .map_err(|_| VrfError::InvalidSecretKey)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VrfError> {
+        let secret_key = SecretKey::from_slice(bytes).map_err(|_| VrfError::InvalidSecretKey)?;
+        let secret = *secret_key.to_nonzero_scalar();
+        let public = VrfPublicKey((ProjectivePoint::GENERATOR * secret).to_affine());
+        Ok(Self { secret, public })
+    }
+
+    pub const fn public_key(&self) -> &VrfPublicKey {
+        &self.public
+    }
+
+    /// Compute the VRF proof for `alpha`
+    pub fn prove(&self, alpha: &[u8]) -> Result<VrfProof, VrfError> {
+        let h = encode_to_curve(&self.public, alpha)?;
+        let gamma = (ProjectivePoint::from(h) * self.secret).to_affine();
+        let k = generate_nonce(&self.secret, &h);
+        let u = (ProjectivePoint::GENERATOR * k).to_affine();
+        let v = (ProjectivePoint::from(h) * k).to_affine();
+        let c = challenge(&[&self.public.0, &h, &gamma, &u, &v]);
+        let s = k + scalar_from_challenge(&c) * self.secret;
+
+        Ok(VrfProof { gamma, c, s })
+    }
+}
+
+impl std::fmt::Debug for VrfSecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VrfSecretKey")
+            .field("public", &self.public)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The public half of the oracle's VRF keypair
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct VrfPublicKey(AffinePoint);
+
+impl VrfPublicKey {
+    /// Parse a SEC1-encoded (compressed or uncompressed) public key.
+    ///
+    /// The identity is rejected (RFC 9381 section 5.4.5): proofs for it can be forged.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VrfError> {
+        let encoded = EncodedPoint::from_bytes(bytes).map_err(|_| VrfError::InvalidPublicKey)?;
+        let point: Option<AffinePoint> = AffinePoint::from_encoded_point(&encoded).into();
+        point
+            .filter(|point| *point != AffinePoint::IDENTITY)
+            .map(Self)
+            .ok_or(VrfError::InvalidPublicKey)
+    }
+
+    pub fn from_hex(value: &str) -> Result<Self, VrfError> {
+        let bytes = hex::decode(value.trim().trim_start_matches("0x"))
+            .map_err(|_| VrfError::InvalidPublicKey)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Compressed SEC1 encoding
+    pub fn to_bytes(self) -> [u8; POINT_LEN] {
+        point_to_bytes(&self.0)
+    }
+
+    /// Verify `proof` for `alpha` and return the VRF output on success
+    pub fn verify(&self, alpha: &[u8], proof: &VrfProof) -> Result<[u8; 32], VrfError> {
+        let h = encode_to_curve(self, alpha)?;
+        let c = scalar_from_challenge(&proof.c);
+        let u =
+            (ProjectivePoint::GENERATOR * proof.s - ProjectivePoint::from(self.0) * c).to_affine();
+        let v = (ProjectivePoint::from(h) * proof.s - ProjectivePoint::from(proof.gamma) * c)
+            .to_affine();
+
+        if challenge(&[&self.0, &h, &proof.gamma, &u, &v]) != proof.c {
+            return Err(VrfError::VerificationFailed);
+        }
+
+        Ok(proof.to_hash())
+    }
+}
+
+impl std::fmt::Debug for VrfPublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "VrfPublicKey(0x{})", hex::encode(self.to_bytes()))
+    }
+}
+
+/// An ECVRF proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VrfProof {
+    gamma: AffinePoint,
+    c: [u8; C_LEN],
+    s: Scalar,
+}
+
+impl VrfProof {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VrfError> {
+        // Only the compressed encoding is canonical
+        if bytes.len() != PROOF_LEN || !matches!(bytes[0], 0x02 | 0x03) {
+            return Err(VrfError::InvalidProof);
+        }
+
+        let encoded =
+            EncodedPoint::from_bytes(&bytes[..POINT_LEN]).map_err(|_| VrfError::InvalidProof)?;
+        let gamma: Option<AffinePoint> = AffinePoint::from_encoded_point(&encoded).into();
+        let gamma = gamma.ok_or(VrfError::InvalidProof)?;
+
+        let mut c = [0u8; C_LEN];
+        c.copy_from_slice(&bytes[POINT_LEN..POINT_LEN + C_LEN]);
+
+        let mut s_bytes = FieldBytes::default();
+        s_bytes.copy_from_slice(&bytes[POINT_LEN + C_LEN..]);
+        let s: Option<Scalar> = Scalar::from_repr(s_bytes).into();
+        let s = s.ok_or(VrfError::InvalidProof)?;
+
+        Ok(Self { gamma, c, s })
+    }
+
+    pub fn to_bytes(&self) -> [u8; PROOF_LEN] {
+        let mut out = [0u8; PROOF_LEN];
+        out[..POINT_LEN].copy_from_slice(&point_to_bytes(&self.gamma));
+        out[POINT_LEN..POINT_LEN + C_LEN].copy_from_slice(&self.c);
+        out[POINT_LEN + C_LEN..].copy_from_slice(&self.s.to_bytes());
+        out
+    }
+
+    /// The VRF output (beta) committed to by this proof
+    pub fn to_hash(&self) -> [u8; 32] {
+        Sha256::new()
+            .chain_update([SUITE_STRING, 0x03])
+            .chain_update(point_to_bytes(&self.gamma))
+            .chain_update([0x00])
+            .finalize()
+            .into()
+    }
+}
+
+fn point_to_bytes(point: &AffinePoint) -> [u8; POINT_LEN] {
+    let mut out = [0u8; POINT_LEN];
+    out.copy_from_slice(point.to_encoded_point(true).as_bytes());
+    out
+}
+
+/// ECVRF_encode_to_curve_try_and_increment (RFC 9381 section 5.4.1.1)
+fn encode_to_curve(public_key: &VrfPublicKey, alpha: &[u8]) -> Result<AffinePoint, VrfError> {
+    let pk_bytes = public_key.to_bytes();
+
+    for ctr in 0..=u8::MAX {
+        let hash = Sha256::new()
+            .chain_update([SUITE_STRING, 0x01])
+            .chain_update(pk_bytes)
+            .chain_update(alpha)
+            .chain_update([ctr, 0x00])
+            .finalize();
+
+        let mut candidate = [0x02u8; POINT_LEN];
+        candidate[1..].copy_from_slice(&hash);
+
+        if let Ok(encoded) = EncodedPoint::from_bytes(candidate) {
+            let point: Option<AffinePoint> = AffinePoint::from_encoded_point(&encoded).into();
+            if let Some(point) = point {
+                return Ok(point);
+            }
+        }
+    }
+
+    Err(VrfError::EncodeToCurveFailed)
+}
+
+/// ECVRF_challenge_generation (RFC 9381 section 5.4.3)
+fn challenge(points: &[&AffinePoint]) -> [u8; C_LEN] {
+    let mut hasher = Sha256::new().chain_update([SUITE_STRING, 0x02]);
+    for point in points {
+        // U and V may be the identity for a forged proof, which encodes as a single byte
+        hasher.update(point.to_encoded_point(true).as_bytes());
+    }
+    let digest = hasher.chain_update([0x00]).finalize();
+
+    let mut c = [0u8; C_LEN];
+    c.copy_from_slice(&digest[..C_LEN]);
+    c
+}
+
+fn scalar_from_challenge(c: &[u8; C_LEN]) -> Scalar {
+    let mut bytes = FieldBytes::default();
+    bytes[32 - C_LEN..].copy_from_slice(c);
+    <Scalar as Reduce<U256>>::reduce_bytes(&bytes)
+}
+
+/// ECVRF_nonce_generation_RFC6979 (RFC 9381 section 5.4.2.1)
+///
+/// RFC 6979 section 3.2 is an HMAC-DRBG seeded with the secret key and the hashed
+/// message, drawn from until the output is a valid non-zero scalar.
+fn generate_nonce(secret: &Scalar, h: &AffinePoint) -> Scalar {
+    let h1 = Sha256::digest(point_to_bytes(h));
+    let h1 = <Scalar as Reduce<U256>>::reduce_bytes(&h1).to_bytes();
+    let mut drbg = HmacDrbg::new(&secret.to_bytes(), &h1, &[]);
+
+    loop {
+        let mut candidate = FieldBytes::default();
+        drbg.fill_bytes(&mut candidate);
+        let nonce: Option<Scalar> = Scalar::from_repr(candidate).into();
+        if let Some(nonce) = nonce {
+            if !bool::from(nonce.is_zero()) {
+                return nonce;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Secret key of the RFC 6979 P-256 examples, valid on secp256k1 too
+    const SECRET_KEY: &str = "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+
+    fn secret_key() -> VrfSecretKey {
+        VrfSecretKey::from_hex(SECRET_KEY).unwrap()
+    }
+
+    #[test]
+    fn prove_then_verify() {
+        let key = secret_key();
+        for alpha in [&b""[..], b"sample", &[0xff; 64]] {
+            let proof = key.prove(alpha).unwrap();
+            let decoded = VrfProof::from_bytes(&proof.to_bytes()).unwrap();
+            assert_eq!(decoded, proof);
+            assert_eq!(
+                key.public_key().verify(alpha, &decoded),
+                Ok(proof.to_hash())
+            );
+        }
+    }
+
+    #[test]
+    fn proofs_are_deterministic() {
+        let key = secret_key();
+        assert_eq!(key.prove(b"sample").unwrap(), key.prove(b"sample").unwrap());
+        assert_ne!(
+            key.prove(b"sample").unwrap().to_hash(),
+            key.prove(b"other").unwrap().to_hash()
+        );
+    }
+
+    // Computed with an independent Python implementation of the same suite
+    #[test]
+    fn known_answer() {
+        let key = secret_key();
+        assert_eq!(
+            hex::encode(key.public_key().to_bytes()),
+            "032c8c31fc9f990c6b55e3865a184a4ce50e09481f2eaeb3e60ec1cea13a6ae645"
+        );
+
+        let proof = key.prove(b"sample").unwrap();
+        assert_eq!(
+            hex::encode(proof.to_bytes()),
+            "0338ec99b5d0f94ebcc2c704c04af3de8b4289df8798e5fb9f920d7f5d77ac03d7\
+             718b9677d1c9348649ac2ec4f7ecbe51\
+             9b30dd10c4eb5efc21dd5944709f2f3b7e97a25f6f095334593502d05103bc5b"
+        );
+        assert_eq!(
+            hex::encode(proof.to_hash()),
+            "d466c22e14dc3b7fd169668dd3ee9ac6351429a24aebc5e8af61a0f0de89b65a"
+        );
+
+        let proof = key.prove(b"").unwrap();
+        assert_eq!(
+            hex::encode(proof.to_hash()),
+            "dcc8f9f13ecef09eab19c58beddf5d1e644eb072af29e6139b7ec89786b4153f"
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_proofs() {
+        let key = secret_key();
+        let public_key = key.public_key();
+        let proof = key.prove(b"sample").unwrap();
+
+        let gamma = VrfProof {
+            gamma: (ProjectivePoint::from(proof.gamma) + ProjectivePoint::GENERATOR).to_affine(),
+            ..proof.clone()
+        };
+        assert_eq!(
+            public_key.verify(b"sample", &gamma),
+            Err(VrfError::VerificationFailed)
+        );
+
+        let mut c = proof.clone();
+        c.c[0] ^= 0x01;
+        assert_eq!(
+            public_key.verify(b"sample", &c),
+            Err(VrfError::VerificationFailed)
+        );
+
+        let s = VrfProof {
+            s: proof.s + Scalar::ONE,
+            ..proof.clone()
+        };
+        assert_eq!(
+            public_key.verify(b"sample", &s),
+            Err(VrfError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn rejects_other_input_or_key() {
+        let key = secret_key();
+        let proof = key.prove(b"sample").unwrap();
+
+        assert_eq!(
+            key.public_key().verify(b"samplf", &proof),
+            Err(VrfError::VerificationFailed)
+        );
+
+        let other = VrfSecretKey::from_bytes(&[0x11; 32]).unwrap();
+        assert_eq!(
+            other.public_key().verify(b"sample", &proof),
+            Err(VrfError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_public_keys() {
+        // The SEC1 encoding of the identity
+        assert_eq!(
+            VrfPublicKey::from_bytes(&[0x00]),
+            Err(VrfError::InvalidPublicKey)
+        );
+
+        let mut not_on_curve = secret_key().public_key().to_bytes();
+        not_on_curve[1..].fill(0xff);
+        assert_eq!(
+            VrfPublicKey::from_bytes(&not_on_curve),
+            Err(VrfError::InvalidPublicKey)
+        );
+
+        let public_key = secret_key().public_key().to_bytes();
+        assert_eq!(
+            VrfPublicKey::from_bytes(&public_key),
+            Ok(*secret_key().public_key())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_proofs() {
+        let bytes = secret_key().prove(b"sample").unwrap().to_bytes();
+
+        assert_eq!(
+            VrfProof::from_bytes(&bytes[..PROOF_LEN - 1]),
+            Err(VrfError::InvalidProof)
+        );
+
+        // s at or above the group order
+        let mut s_overflow = bytes;
+        s_overflow[POINT_LEN + C_LEN..].fill(0xff);
+        assert_eq!(
+            VrfProof::from_bytes(&s_overflow),
+            Err(VrfError::InvalidProof)
+        );
+
+        // gamma not on the curve
+        let mut gamma_invalid = bytes;
+        gamma_invalid[1..POINT_LEN].fill(0xff);
+        assert_eq!(
+            VrfProof::from_bytes(&gamma_invalid),
+            Err(VrfError::InvalidProof)
+        );
+
+        // gamma in a non-compressed encoding
+        let mut gamma_compact = bytes;
+        gamma_compact[0] = 0x05;
+        assert_eq!(
+            VrfProof::from_bytes(&gamma_compact),
+            Err(VrfError::InvalidProof)
+        );
+    }
+}
@@ -1,22 +1,50 @@
-use crate::database::QueueDatabase;
-use crate::oracle;
-use crate::relayer::{Relayer, RelayerConfig};
+use crate::database::{
+    AttemptOutcome, FulfillmentAttempt, KeyLocks, PendingRequest, QueueDatabase, QueueListener,
+    SentAttempt,
+};
+use crate::oracle::source::{self, RandomnessSource};
+use crate::oracle::{self, Call, CallFailure};
+use crate::relayer::{
+    BatchSizer, BroadcastStatus, FulfillmentMode, Relayer, RelayerAccount, RelayerConfig,
+    RelayerError, SentBatch,
+};
+use crate::shutdown::Shutdown;
+use alloy::primitives::{Address, FixedBytes, TxHash};
+use alloy::rpc::types::TransactionReceipt;
 use alloy::sol_types::SolCall;
 use rindexer::PostgresClient;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
+use tokio::task::{self, AbortHandle, JoinError, JoinSet};
 use tokio::time;
-use tracing::{error, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 pub struct QueueProcessor {
     queue_db: QueueDatabase,
     poll_interval: Duration,
     relayer: Option<Arc<Relayer>>,
+    randomness_source: Option<Arc<dyn RandomnessSource>>,
     last_empty_log: Arc<Mutex<Option<Instant>>>,
 }
 
-const MAX_BATCH_SIZE: usize = 100;
+/// How long to wait before retrying when every relayer account is busy
+const ACCOUNT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Most requests a single-mode account takes at once. Each is its own transaction,
+/// confirmed before the next is sent, and the last must be sent well before the
+/// stale-processing timeout of `dequeue_requests` hands the requests to another account.
+const SINGLE_MODE_MAX_REQUESTS: usize = 4;
+
+type BatchResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+/// Relayer, requests and abort handle of each batch task
+type InFlightBatches = HashMap<task::Id, (Address, Vec<FixedBytes<32>>, AbortHandle)>;
+
+/// Transaction a previous owner of the key left pending, with the requests it fulfills
+type OrphanedBatch = (Arc<RelayerAccount>, SentBatch, Vec<PendingRequest>);
 
 impl QueueProcessor {
     pub fn new(postgres_client: Arc<PostgresClient>, poll_interval_millis: u64) -> Self {
@@ -24,6 +52,7 @@ impl QueueProcessor {
             queue_db: QueueDatabase::new(postgres_client),
             poll_interval: Duration::from_millis(poll_interval_millis),
             relayer: None,
+            randomness_source: None,
             last_empty_log: Arc::new(Mutex::new(None)),
         }
     }
@@ -38,6 +67,10 @@ impl QueueProcessor {
                     "Loaded relayer config with {} accounts",
                     config.accounts.len()
                 );
+                let randomness_source = source::from_config(&config.randomness_source)?;
+                info!("Using {} randomness source", randomness_source.name());
+                self.randomness_source = Some(randomness_source);
+
                 let relayer = Arc::new(Relayer::new(config).await?);
                 self.relayer = Some(relayer);
                 Ok(())
@@ -53,11 +86,18 @@ impl QueueProcessor {
 
     /// Run database migrations
     pub async fn run_migrations(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.queue_db.run_migration().await
+        self.queue_db.run_migrations().await
     }
 
-    /// Start processing the queue
-    pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// Start processing the queue until `shutdown` is triggered.
+    ///
+    /// On shutdown no more requests are dequeued, in-flight batches get
+    /// `shutdown.drain_timeout` to be mined, and requests that were never broadcast go
+    /// back to the queue.
+    pub async fn start(
+        &mut self,
+        shutdown: Shutdown,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Initialize relayer if not already done
         if self.relayer.is_none() {
             self.init_relayer().await?;
@@ -69,26 +109,109 @@ impl QueueProcessor {
             .ok_or("Failed to initialize relayer")?
             .clone();
 
+        let randomness_source = self
+            .randomness_source
+            .as_ref()
+            .ok_or("Failed to initialize randomness source")?
+            .clone();
+
         info!(
             "Starting queue processor with {} relayer accounts",
             relayer.get_addresses().len()
         );
+        relayer.spawn_nonce_monitor();
+        relayer.spawn_funder(self.queue_db.clone());
 
-        // Check if BEBE is configured
-        let use_batching = relayer.accounts.iter().any(|a| a.bebe_address.is_some());
-        if !use_batching {
-            return Err("BEBE not configured. Batch processing requires BEBE to be deployed and configured.".into());
+        if let Some(account) = relayer.accounts.first() {
+            info!("Fulfillment mode: {:?}", account.fulfillment_mode);
         }
 
-        loop {
-            // Sleep for the poll interval
-            time::sleep(self.poll_interval).await;
+        // Wake up on new requests instead of waiting for the next poll, and share the
+        // keys with other replicas through advisory locks
+        let (listener, locks) = match std::env::var("DATABASE_URL") {
+            Ok(database_url) => (
+                Some(QueueListener::spawn(database_url.clone())),
+                KeyLocks::spawn(database_url, relayer.key_addresses()),
+            ),
+            Err(_) => {
+                warn!(
+                    "DATABASE_URL not set, falling back to polling every {:?} and not coordinating keys with other replicas",
+                    self.poll_interval
+                );
+                (None, KeyLocks::unlocked(relayer.key_addresses()))
+            }
+        };
+
+        let mut owned_keys = HashSet::new();
+        let mut in_flight = JoinSet::new();
+        let mut in_flight_batches = HashMap::new();
+        let mut queue_empty = false;
+
+        while !shutdown.is_triggered() {
+            let orphans = Self::sync_owned_keys(
+                &locks,
+                &mut owned_keys,
+                &relayer,
+                &self.queue_db,
+                &in_flight_batches,
+            )
+            .await;
+            Self::resume_orphans(
+                orphans,
+                &relayer,
+                &self.queue_db,
+                &mut in_flight,
+                &mut in_flight_batches,
+            )
+            .await;
+
+            // Release the accounts of batches that have finished
+            while let Some(result) = in_flight.try_join_next_with_id() {
+                Self::finish_batch(result, &mut in_flight_batches, &relayer, &self.queue_db).await;
+            }
+
+            // Once the queue is drained, wait for a notification, the poll interval or a batch to end
+            if queue_empty {
+                let wait_for_requests = async {
+                    match &listener {
+                        Some(listener) => listener.wait(self.poll_interval).await,
+                        None => time::sleep(self.poll_interval).await,
+                    }
+                };
+                tokio::select! {
+                    () = wait_for_requests => {}
+                    () = shutdown.wait() => continue,
+                    Some(result) = in_flight.join_next_with_id() => {
+                        Self::finish_batch(
+                            result,
+                            &mut in_flight_batches,
+                            &relayer,
+                            &self.queue_db,
+                        )
+                        .await;
+                    }
+                }
+                queue_empty = false;
+            }
 
             // 1. Check if there's an available relayer
             let available_account = match relayer.try_get_available_batch().await {
                 Some(account) => account,
                 None => {
                     trace!("No available relayer accounts, waiting...");
+                    // Wait for an in-flight batch to free its account, or retry shortly
+                    tokio::select! {
+                        () = time::sleep(ACCOUNT_RETRY_DELAY) => {}
+                        Some(result) = in_flight.join_next_with_id() => {
+                            Self::finish_batch(
+                                result,
+                                &mut in_flight_batches,
+                                &relayer,
+                                &self.queue_db,
+                            )
+                            .await;
+                        }
+                    }
                     continue;
                 }
             };
@@ -102,6 +225,7 @@ impl QueueProcessor {
                 Err(e) => {
                     error!("Failed to get pending count: {}", e);
                     relayer.release_account(account_address).await;
+                    queue_empty = true;
                     continue;
                 }
             };
@@ -117,135 +241,991 @@ impl QueueProcessor {
                     *last_log = Some(now);
                 }
                 relayer.release_account(account_address).await;
+                queue_empty = true;
                 continue;
             }
 
-            // Dequeue ALL pending requests (up to a reasonable limit to avoid memory issues)
-            let max_batch_size = 100; // Reasonable limit for a single multicall
-            let requests_to_dequeue = std::cmp::min(pending_count as usize, max_batch_size);
+            // Split the backlog across this account and the others that could take a batch now
+            let idle_accounts = relayer.claimable_accounts().await + 1;
+            let requests_to_dequeue =
+                (pending_count as usize)
+                    .div_ceil(idle_accounts)
+                    .min(Self::max_requests(
+                        available_account.fulfillment_mode,
+                        &relayer.batch_sizer,
+                    ));
+
+            // Only requests whose block has enough confirmations are eligible
+            let confirmed_block = match relayer.confirmed_block().await {
+                Ok(block) => block,
+                Err(e) => {
+                    error!("Failed to get the latest block: {}", e);
+                    relayer.release_account(account_address).await;
+                    queue_empty = true;
+                    continue;
+                }
+            };
 
-            let requests = match self.queue_db.dequeue_requests(requests_to_dequeue).await {
+            let requests = match self
+                .queue_db
+                .dequeue_requests(requests_to_dequeue, confirmed_block)
+                .await
+            {
                 Ok(reqs) => reqs,
                 Err(e) => {
                     error!("Failed to dequeue requests: {:?}", e);
                     relayer.release_account(account_address).await;
+                    queue_empty = true;
                     continue;
                 }
             };
 
             if requests.is_empty() {
                 relayer.release_account(account_address).await;
+                queue_empty = true;
                 continue;
             }
 
+            let requests = match Self::cancel_reorged(requests, &relayer, &self.queue_db).await {
+                Ok(requests) if !requests.is_empty() => requests,
+                Ok(_) => {
+                    relayer.release_account(account_address).await;
+                    continue;
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to check requests against the canonical chain: {}",
+                        e
+                    );
+                    relayer.release_account(account_address).await;
+                    queue_empty = true;
+                    continue;
+                }
+            };
+
             info!(
-                "Processing {} requests with relayer {}",
+                "Processing {} requests with relayer {} ({} batches in flight)",
                 requests.len(),
-                account_address
+                account_address,
+                in_flight.len() + 1
             );
 
-            // 3. Process all requests in a single multicall
+            // 3. Send the slice as one batch without waiting for its receipt
+            let request_ids: Vec<_> = requests.iter().map(|r| r.request_id).collect();
             let queue_db = self.queue_db.clone();
-            let result = Self::process_all_requests(requests, queue_db, available_account).await;
+            let randomness_source = randomness_source.clone();
+            let batch_sizer = relayer.batch_sizer.clone();
+            let isolate_failures = relayer.isolate_failures;
+            let batch_shutdown = shutdown.clone();
+            let handle = in_flight.spawn(async move {
+                Self::process_all_requests(
+                    requests,
+                    queue_db,
+                    available_account,
+                    randomness_source.as_ref(),
+                    &batch_sizer,
+                    isolate_failures,
+                    &batch_shutdown,
+                )
+                .await
+            });
+            in_flight_batches.insert(handle.id(), (account_address, request_ids, handle));
+        }
+
+        Self::drain(
+            in_flight,
+            &mut in_flight_batches,
+            &relayer,
+            &self.queue_db,
+            shutdown.drain_timeout,
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// Cancel the requests whose block is no longer canonical and return the others.
+    ///
+    /// Requests enqueued before block numbers were recorded are not checked. A request
+    /// whose block was reorged out is only cancelled once its oracle no longer knows it;
+    /// one the oracle still knows was mined again elsewhere and goes back to the queue
+    /// until the indexer records its new block.
+    /// If the chain cannot be read, every request goes back to the queue.
+    async fn cancel_reorged(
+        requests: Vec<PendingRequest>,
+        relayer: &Relayer,
+        queue_db: &QueueDatabase,
+    ) -> Result<Vec<PendingRequest>, Box<dyn std::error::Error + Send + Sync>> {
+        let request_ids: Vec<_> = requests.iter().map(|r| r.request_id).collect();
+
+        let mut canonical: HashMap<u64, FixedBytes<32>> = HashMap::new();
+        for number in requests.iter().filter_map(|request| request.block_number) {
+            if canonical.contains_key(&number) {
+                continue;
+            }
+            match relayer.canonical_block_hash(number).await {
+                Ok(hash) => {
+                    canonical.insert(number, hash);
+                }
+                Err(e) => {
+                    queue_db.requeue_unsent(&request_ids).await?;
+                    return Err(e);
+                }
+            }
+        }
+
+        let (confirmed, reorged): (Vec<_>, Vec<_>) = requests.into_iter().partition(|request| {
+            request
+                .block_number
+                .is_none_or(|number| canonical[&number] == request.block_hash)
+        });
+        if reorged.is_empty() {
+            return Ok(confirmed);
+        }
+
+        let mut gone = Vec::new();
+        let mut remined = Vec::new();
+        for request in reorged {
+            match relayer
+                .request_state(request.contract_address, request.request_id)
+                .await
+            {
+                Ok(None) => {
+                    warn!(
+                        "Block {} of request {} was reorged out and the oracle no longer knows it, cancelling it",
+                        request.block_hash,
+                        hex::encode(request.request_id)
+                    );
+                    gone.push(request);
+                }
+                Ok(Some(true)) => {
+                    info!(
+                        "Block {} of request {} was reorged out but the request is fulfilled on-chain",
+                        request.block_hash,
+                        hex::encode(request.request_id)
+                    );
+                    queue_db.mark_fulfilled(request.request_id).await?;
+                }
+                Ok(Some(false)) => {
+                    warn!(
+                        "Block {} of request {} was reorged out but the oracle still knows it, waiting for it to be indexed again",
+                        request.block_hash,
+                        hex::encode(request.request_id)
+                    );
+                    remined.push(request.request_id);
+                }
+                Err(e) => {
+                    queue_db.requeue_unsent(&request_ids).await?;
+                    return Err(e);
+                }
+            }
+        }
+
+        queue_db
+            .cancel_requests(&gone, "Request block was reorged out")
+            .await?;
+
+        // Requests indexed again in another block meanwhile go back to the queue, and so do
+        // the ones still on-chain, without counting the attempt
+        let gone_ids: Vec<_> = gone.iter().map(|r| r.request_id).collect();
+        queue_db.requeue_unsent(&gone_ids).await?;
+        queue_db.requeue_unsent(&remined).await?;
+
+        Ok(confirmed)
+    }
+
+    /// Wait for in-flight batches until `timeout`, then abort the rest
+    async fn drain(
+        mut in_flight: JoinSet<BatchResult>,
+        in_flight_batches: &mut InFlightBatches,
+        relayer: &Relayer,
+        queue_db: &QueueDatabase,
+        timeout: Duration,
+    ) {
+        info!(
+            "Stopped dequeuing, waiting for {} in-flight batches",
+            in_flight.len()
+        );
+
+        let deadline = time::sleep(timeout);
+        tokio::pin!(deadline);
+
+        loop {
+            tokio::select! {
+                result = in_flight.join_next_with_id() => match result {
+                    Some(result) => {
+                        Self::finish_batch(result, in_flight_batches, relayer, queue_db).await;
+                    }
+                    None => break,
+                },
+                () = &mut deadline => {
+                    warn!(
+                        "{} batches still in flight after {:?}, aborting them",
+                        in_flight.len(),
+                        timeout
+                    );
+                    in_flight.abort_all();
+                    while let Some(result) = in_flight.join_next_with_id().await {
+                        Self::finish_batch(result, in_flight_batches, relayer, queue_db).await;
+                    }
+                    break;
+                }
+            }
+        }
 
-            // Always release the account after processing
-            relayer.release_account(account_address).await;
+        info!("Queue processor stopped");
+    }
+
+    /// Release the account of a finished batch and log its outcome
+    async fn finish_batch(
+        result: Result<(task::Id, BatchResult), JoinError>,
+        in_flight_batches: &mut InFlightBatches,
+        relayer: &Relayer,
+        queue_db: &QueueDatabase,
+    ) {
+        let (id, outcome, interrupted) = match result {
+            Ok((id, outcome)) => (id, outcome.map_err(|e| format!("{e:?}")), false),
+            Err(e) if e.is_cancelled() => (e.id(), Err("batch task aborted".to_string()), true),
+            Err(e) => (e.id(), Err(format!("batch task panicked: {e}")), true),
+        };
 
-            if let Err(e) = result {
-                error!("Failed to process requests: {:?}", e);
+        if let Some((address, request_ids, _)) = in_flight_batches.remove(&id) {
+            relayer.release_account(address).await;
+
+            // Broadcast requests stay in processing, their transaction may still be mined
+            if interrupted {
+                if let Err(e) = queue_db.requeue_unsent(&request_ids).await {
+                    error!("Failed to requeue requests of interrupted batch: {}", e);
+                }
             }
         }
+
+        if let Err(e) = outcome {
+            error!("Failed to process requests: {}", e);
+        }
     }
 
-    /// Process all requests in a single multicall
+    /// Process all requests, split into as few batches as the gas budget allows
     async fn process_all_requests(
-        requests: Vec<crate::database::PendingRequest>,
+        requests: Vec<PendingRequest>,
         queue_db: QueueDatabase,
-        account: Arc<crate::relayer::RelayerAccount>,
+        account: Arc<RelayerAccount>,
+        randomness_source: &dyn RandomnessSource,
+        batch_sizer: &BatchSizer,
+        isolate_failures: bool,
+        shutdown: &Shutdown,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         if requests.is_empty() {
             return Ok(());
         }
 
-        let batch_size = requests.len();
         let request_ids: Vec<_> = requests.iter().map(|r| r.request_id).collect();
 
-        info!("Processing batch of {} requests", batch_size);
+        info!("Processing batch of {} requests", requests.len());
 
         // Build batch calls
-        let calls = oracle::build_batch_calls(&requests);
-
-        // Send batch transaction
-        match account.send_batch(&calls).await {
-            Ok(tx_hash) => {
-                info!("Batch transaction sent: {}", tx_hash);
-
-                // Record metrics for batch fulfillment
-                crate::relayer::metrics::record_batch_fulfillment(batch_size);
-
-                // Wait a bit for transaction to be mined
-                time::sleep(Duration::from_secs(2)).await;
-
-                // Check which requests were actually fulfilled and only mark those as completed
-                // let mut fulfilled_requests = Vec::new();
-                // let mut unfulfilled_requests = Vec::new();
-
-                // for request in requests.iter() {
-                //     let encoded_call = oracle::encode_get_randomness_call(request.request_id);
-                //     match account.send_call(request.contract_address, encoded_call.abi_encode().into()).await {
-                //         Ok(call_result) => {
-                //             let call_res_array = call_result.as_ref();
-                //             match oracle::IVRFOracle::getRandomnessCall::abi_decode_returns(call_res_array) {
-                //                 Ok(decoded_result) => {
-                //                     if decoded_result.fulfilled {
-                //                         fulfilled_requests.push(request.request_id);
-                //                     } else {
-                //                         crate::relayer::metrics::record_batch_unfulfilled(1);
-                //                         unfulfilled_requests.push(request.request_id);
-                //                     }
-                //                 }
-                //                 Err(e) => {
-                //                     error!("Failed to decode call result for request {}: {:?}", hex::encode(request.request_id), e);
-                //                     unfulfilled_requests.push(request.request_id);
-                //                 }
-                //             }
-                //         }
-                //         Err(e) => {
-                //             error!("Failed to send call for request {}: {:?}", hex::encode(request.request_id), e);
-                //             unfulfilled_requests.push(request.request_id);
-                //         }
-                //     }
-                // }
-
-                // Mark only the fulfilled requests as completed
-                for request_id in request_ids.iter() {
-                    queue_db.mark_fulfilled(*request_id).await?;
+        let calls = match oracle::build_batch_calls(&requests, randomness_source) {
+            Ok(calls) => calls,
+            Err(e) => {
+                let error_msg = format!("Failed to generate randomness: {e}");
+                queue_db
+                    .record_attempts(
+                        &requests,
+                        &FulfillmentAttempt::failed(account.address, error_msg.clone()),
+                    )
+                    .await?;
+                queue_db.mark_batch_failed(&request_ids, &error_msg).await?;
+                return Err(e.into());
+            }
+        };
+
+        let batch: Vec<_> = requests.into_iter().zip(calls).collect();
+        let batches = Self::plan_batches(batch, account.fulfillment_mode, batch_sizer, |calls| {
+            let account = &account;
+            async move { account.estimate_batch_gas(&calls).await }
+        })
+        .await;
+
+        // Send every part even if an earlier one hit a database error
+        let mut result = Ok(());
+        let mut batches = batches.into_iter();
+        while let Some(batch) = batches.next() {
+            // Parts not yet broadcast go back to the queue on shutdown
+            if shutdown.is_triggered() {
+                let unsent: Vec<_> = std::iter::once(batch)
+                    .chain(batches)
+                    .flatten()
+                    .map(|(request, _)| request.request_id)
+                    .collect();
+                queue_db.requeue_unsent(&unsent).await?;
+                break;
+            }
+
+            let (requests, calls): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+            if let Err(e) = Self::send_batch(
+                requests,
+                calls,
+                &queue_db,
+                &account,
+                batch_sizer,
+                isolate_failures,
+                shutdown,
+            )
+            .await
+            {
+                error!("Failed to record batch outcome: {:?}", e);
+                result = Err(e);
+            }
+        }
+
+        result
+    }
+
+    /// Most requests to dequeue for one account in `mode`
+    fn max_requests(mode: FulfillmentMode, batch_sizer: &BatchSizer) -> usize {
+        match mode {
+            FulfillmentMode::Single => SINGLE_MODE_MAX_REQUESTS,
+            FulfillmentMode::Batch | FulfillmentMode::Multicall3(_) => batch_sizer.max_calls(),
+        }
+    }
+
+    /// Split requests into the transactions of `mode`
+    async fn plan_batches<F, Fut>(
+        batch: Vec<(PendingRequest, Call)>,
+        mode: FulfillmentMode,
+        batch_sizer: &BatchSizer,
+        estimate: F,
+    ) -> Vec<Vec<(PendingRequest, Call)>>
+    where
+        F: Fn(Vec<Call>) -> Fut,
+        Fut: Future<Output = Result<u64, RelayerError>>,
+    {
+        match mode {
+            // One transaction per request
+            FulfillmentMode::Single => batch.into_iter().map(|call| vec![call]).collect(),
+            FulfillmentMode::Batch | FulfillmentMode::Multicall3(_) => {
+                Self::split_to_gas_budget(batch, batch_sizer, estimate).await
+            }
+        }
+    }
+
+    /// Split a batch in halves until the gas `estimate` of every part fits the budget
+    async fn split_to_gas_budget<F, Fut>(
+        batch: Vec<(PendingRequest, Call)>,
+        batch_sizer: &BatchSizer,
+        estimate: F,
+    ) -> Vec<Vec<(PendingRequest, Call)>>
+    where
+        F: Fn(Vec<Call>) -> Fut,
+        Fut: Future<Output = Result<u64, RelayerError>>,
+    {
+        let mut remaining = vec![batch];
+        let mut batches = Vec::new();
+
+        while let Some(mut batch) = remaining.pop() {
+            if batch.len() > 1 {
+                let calls: Vec<_> = batch.iter().map(|(_, call)| call.clone()).collect();
+                match estimate(calls).await {
+                    Ok(gas) if !batch_sizer.fits(gas) => {
+                        info!(
+                            "Batch of {} calls needs {} gas, over the {} budget; splitting",
+                            batch.len(),
+                            gas,
+                            batch_sizer.gas_budget()
+                        );
+                        let second_half = batch.split_off(batch.len() / 2);
+                        remaining.push(second_half);
+                        remaining.push(batch);
+                        continue;
+                    }
+                    Ok(_) => {}
+                    // Reverts are reported when the batch is sent
+                    Err(e) => debug!(
+                        "Failed to estimate gas for batch of {} calls: {}",
+                        batch.len(),
+                        e
+                    ),
                 }
+            }
+            batches.push(batch);
+        }
 
-                // // Put unfulfilled requests back in the queue for retry
-                // for request_id in unfulfilled_requests.iter() {
-                //     queue_db.requeue_request(*request_id).await?;
-                // }
+        batches
+    }
 
-                // info!(
-                //     "Batch processing complete: {} succeeded, {} failed/retrying. Used account {}",
-                //     fulfilled_requests.len(), unfulfilled_requests.len(), account.address
-                // );
-                Ok(())
+    /// Send one batch transaction and record its outcome.
+    ///
+    /// If the batch fails and `isolate_failures` is set, requests that revert on their
+    /// own are dropped or quarantined and the rest is resent within the same attempt.
+    async fn send_batch(
+        mut requests: Vec<PendingRequest>,
+        mut calls: Vec<Call>,
+        queue_db: &QueueDatabase,
+        account: &RelayerAccount,
+        batch_sizer: &BatchSizer,
+        mut isolate_failures: bool,
+        shutdown: &Shutdown,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        loop {
+            let batch_size = requests.len();
+
+            // Price for the longest-waiting request of the batch
+            let queue_age = requests
+                .iter()
+                .map(|r| r.queued_for)
+                .max()
+                .unwrap_or_default();
+
+            let (tx_hash, nonce, result) = match account.broadcast_batch(&calls, queue_age).await {
+                Ok(sent) => {
+                    // A restart must not send these requests again while this may be mined
+                    let attempt =
+                        FulfillmentAttempt::sent(account.address, sent.tx_hash, Some(sent.nonce));
+                    if let Err(e) = queue_db.record_attempts(&requests, &attempt).await {
+                        warn!("Failed to record broadcast of {}: {}", sent.tx_hash, e);
+                    }
+                    (
+                        Some(sent.tx_hash),
+                        Some(sent.nonce),
+                        account.confirm_batch(&sent).await,
+                    )
+                }
+                Err(e) => (None, None, Err(e)),
+            };
+
+            let attempt = Self::confirmed_attempt(account.address, tx_hash, nonce, &result);
+            if let Err(e) = queue_db.record_attempts(&requests, &attempt).await {
+                warn!("Failed to record fulfillment attempt: {}", e);
+            }
+
+            match result {
+                Ok(receipt) => {
+                    info!("Batch transaction mined: {}", receipt.transaction_hash);
+
+                    // Record metrics for batch fulfillment
+                    crate::relayer::metrics::record_batch_fulfillment(batch_size);
+                    batch_sizer.record_receipt(calls.len(), receipt.gas_used);
+
+                    return Self::reconcile_batch(&requests, &receipt, queue_db, account).await;
+                }
+                Err(e) => {
+                    warn!("Failed to fulfill batch of {} requests: {}", batch_size, e);
+
+                    // Isolate at most once, a second failure is not caused by the poison requests.
+                    // Nothing is resent once shutdown has started.
+                    if isolate_failures && e.is_revert() && !shutdown.is_triggered() {
+                        isolate_failures = false;
+                        if let Some((remaining_requests, remaining_calls)) =
+                            Self::isolate_poison_requests(
+                                requests.clone(),
+                                calls.clone(),
+                                queue_db,
+                                account,
+                            )
+                            .await?
+                        {
+                            if remaining_requests.is_empty() {
+                                return Ok(());
+                            }
+                            info!(
+                                "Resending the {} remaining requests of the batch",
+                                remaining_requests.len()
+                            );
+                            requests = remaining_requests;
+                            calls = remaining_calls;
+                            continue;
+                        }
+                    }
+
+                    // Mark all requests as failed (will retry if under max retries)
+                    let error_msg = format!("Failed to fulfill batch: {e}");
+                    let request_ids: Vec<_> = requests.iter().map(|r| r.request_id).collect();
+                    queue_db.mark_batch_failed(&request_ids, &error_msg).await?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Drop or quarantine the requests that make a batch revert.
+    ///
+    /// Requests already fulfilled on-chain are marked fulfilled, other reverting requests
+    /// are quarantined. Returns the rest of the batch, or `None` if the failure is not
+    /// caused by individual requests.
+    async fn isolate_poison_requests(
+        requests: Vec<PendingRequest>,
+        calls: Vec<Call>,
+        queue_db: &QueueDatabase,
+        account: &RelayerAccount,
+    ) -> Result<Option<(Vec<PendingRequest>, Vec<Call>)>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        let Some(offenders) = Self::find_poison_requests(&calls, account).await else {
+            return Ok(None);
+        };
+
+        // Every call reverts when the relayer is not authorized, that is not a request issue
+        if offenders
+            .iter()
+            .any(|(_, failure)| *failure == CallFailure::OnlyOracle)
+        {
+            warn!("Relayer {} is not authorized to fulfill", account.address);
+            return Ok(None);
+        }
+
+        let mut poisoned = vec![false; requests.len()];
+        for (index, failure) in offenders {
+            poisoned[index] = true;
+            let request_id = requests[index].request_id;
+            warn!(
+                "Request {} reverts on its own: {}",
+                hex::encode(request_id),
+                failure
+            );
+
+            match failure {
+                CallFailure::AlreadyFulfilled => queue_db.mark_fulfilled(request_id).await?,
+                failure => {
+                    let reason = format!("Fulfillment reverts: {failure}");
+                    queue_db.quarantine_requests(&[request_id], &reason).await?;
+                }
             }
+        }
+
+        let remaining = requests
+            .into_iter()
+            .zip(calls)
+            .zip(poisoned)
+            .filter(|(_, poisoned)| !poisoned)
+            .map(|(request_and_call, _)| request_and_call)
+            .unzip();
+
+        Ok(Some(remaining))
+    }
+
+    /// Bisect a batch with `eth_call` simulations to find the calls that revert.
+    ///
+    /// Returns `None` if the whole batch simulates cleanly or cannot be simulated.
+    async fn find_poison_requests(
+        calls: &[Call],
+        account: &RelayerAccount,
+    ) -> Option<Vec<(usize, CallFailure)>> {
+        let mut offenders = Vec::new();
+        let mut ranges = Vec::new();
+        ranges.push(0..calls.len());
+
+        while let Some(range) = ranges.pop() {
+            let revert_data = match account.simulate_batch(&calls[range.clone()]).await {
+                Ok(Some(revert_data)) => revert_data,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Failed to simulate batch: {}", e);
+                    return None;
+                }
+            };
+
+            if range.len() == 1 {
+                offenders.push((range.start, oracle::decode_revert(&revert_data)));
+            } else {
+                let mid = range.start + range.len() / 2;
+                ranges.push(mid..range.end);
+                ranges.push(range.start..mid);
+            }
+        }
+
+        (!offenders.is_empty()).then_some(offenders)
+    }
+
+    /// Follow the key locks: stop signing with keys whose lock was lost and abort their
+    /// in-flight batches, and before signing with newly locked ones, resync their nonces,
+    /// delegate them to BEBE and settle what their previous owner left unconfirmed. Keys
+    /// whose nonce cannot be read are retried next time.
+    ///
+    /// Returns the transactions the previous owner left pending, to be confirmed.
+    async fn sync_owned_keys(
+        locks: &KeyLocks,
+        owned_keys: &mut HashSet<Address>,
+        relayer: &Relayer,
+        queue_db: &QueueDatabase,
+        in_flight_batches: &InFlightBatches,
+    ) -> Vec<OrphanedBatch> {
+        let held = locks.held();
+        if held == *owned_keys {
+            return Vec::new();
+        }
+
+        for address in owned_keys.difference(&held) {
+            warn!(
+                "Lost the lock on key {}, no longer signing with it",
+                address
+            );
+            relayer.set_owned(*address, false).await;
+
+            // Their fee bumps would race the new owner, which confirms them instead
+            for (batch_address, _, handle) in in_flight_batches.values() {
+                if batch_address == address {
+                    handle.abort();
+                }
+            }
+        }
+        owned_keys.retain(|address| held.contains(address));
+
+        let mut acquired: HashSet<Address> = held.difference(owned_keys).copied().collect();
+        for account in relayer.accounts.iter() {
+            if !acquired.contains(&account.address) {
+                continue;
+            }
+            if let Err(e) = account.sync_nonce().await {
+                warn!("Failed to sync nonce of {}: {}", account.address, e);
+                acquired.remove(&account.address);
+            }
+        }
+        if acquired.is_empty() {
+            return Vec::new();
+        }
+
+        // New keys need the BEBE delegation before they can send batches
+        relayer
+            .delegate(&acquired.iter().copied().collect::<Vec<_>>())
+            .await;
+
+        let orphans = match Self::reconcile_unconfirmed(queue_db, relayer, &acquired).await {
+            Ok(orphans) => orphans,
             Err(e) => {
-                let error_msg = format!("Failed to fulfill batch: {e:?}");
+                error!("Failed to reconcile unconfirmed fulfillments: {}", e);
+                Vec::new()
+            }
+        };
+
+        for address in acquired {
+            info!("Signing with key {}", address);
+            relayer.set_owned(address, true).await;
+            owned_keys.insert(address);
+        }
+
+        orphans
+    }
+
+    /// Settle requests whose transaction was broadcast by a previous run or another
+    /// replica from one of `keys`, but never confirmed.
+    ///
+    /// Mined transactions are reconciled like a fresh batch, and the requests of dropped
+    /// or replaced ones are checked with `getRandomness`. Transactions still pending are
+    /// returned, with their nonce, for their confirmation to be resumed.
+    async fn reconcile_unconfirmed(
+        queue_db: &QueueDatabase,
+        relayer: &Relayer,
+        keys: &HashSet<Address>,
+    ) -> Result<Vec<OrphanedBatch>, Box<dyn std::error::Error + Send + Sync>> {
+        let configured = relayer.get_addresses();
+        // Relayers no longer configured anywhere are reported below
+        let attempts: Vec<_> = queue_db
+            .get_unconfirmed_attempts()
+            .await?
+            .into_iter()
+            .filter(|(_, sent)| keys.contains(&sent.relayer) || !configured.contains(&sent.relayer))
+            .collect();
+        if attempts.is_empty() {
+            return Ok(Vec::new());
+        }
+        info!(
+            "Reconciling {} requests broadcast by a previous owner of their relayer",
+            attempts.len()
+        );
+
+        let mut transactions: HashMap<TxHash, (SentAttempt, Vec<PendingRequest>)> = HashMap::new();
+        let mut orphans = Vec::new();
+        for (request, sent) in attempts {
+            transactions
+                .entry(sent.tx_hash)
+                .or_insert_with(|| (sent, Vec::new()))
+                .1
+                .push(request);
+        }
+
+        for (tx_hash, (sent, requests)) in transactions {
+            let Some(account) = relayer
+                .accounts
+                .iter()
+                .find(|account| account.address == sent.relayer)
+            else {
                 warn!(
-                    "Failed to fulfill batch of {} requests: {:?}",
-                    batch_size, e
+                    "Relayer {} of transaction {} is no longer configured, leaving its {} requests to the processing timeout",
+                    sent.relayer,
+                    tx_hash,
+                    requests.len()
                 );
+                continue;
+            };
 
-                // Mark all requests as failed (will retry if under max retries)
-                queue_db.mark_batch_failed(&request_ids, &error_msg).await?;
+            let status = match account.broadcast_status(tx_hash, sent.nonce).await {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!(
+                        "Failed to check transaction {}, leaving its {} requests to the processing timeout: {}",
+                        tx_hash,
+                        requests.len(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            match status {
+                BroadcastStatus::Mined(receipt) => {
+                    info!(
+                        "Transaction {} was mined, reconciling its {} requests",
+                        tx_hash,
+                        requests.len()
+                    );
+                    queue_db
+                        .record_attempts(
+                            &requests,
+                            &FulfillmentAttempt::mined(sent.relayer, sent.nonce, &receipt),
+                        )
+                        .await?;
+                    Self::reconcile_batch(&requests, &receipt, queue_db, account).await?;
+                }
+                BroadcastStatus::Pending(sent) => {
+                    orphans.push((account.clone(), *sent, requests));
+                }
+                BroadcastStatus::Dropped => {
+                    info!(
+                        "Transaction {} was dropped or replaced, checking its {} requests on-chain",
+                        tx_hash,
+                        requests.len()
+                    );
+                    let attempt = FulfillmentAttempt {
+                        tx_hash: Some(tx_hash),
+                        nonce: sent.nonce,
+                        outcome: AttemptOutcome::Dropped,
+                        ..FulfillmentAttempt::failed(
+                            sent.relayer,
+                            "Transaction dropped before it was confirmed".to_string(),
+                        )
+                    };
+                    queue_db.record_attempts(&requests, &attempt).await?;
+                    Self::reconcile_requests(
+                        &requests,
+                        &HashSet::new(),
+                        tx_hash,
+                        queue_db,
+                        account,
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        Ok(orphans)
+    }
+
+    /// Attempt recording how the confirmation of a batch ended
+    fn confirmed_attempt(
+        relayer: Address,
+        tx_hash: Option<TxHash>,
+        nonce: Option<u64>,
+        result: &Result<TransactionReceipt, RelayerError>,
+    ) -> FulfillmentAttempt {
+        match result {
+            Ok(receipt) => FulfillmentAttempt::mined(relayer, nonce, receipt),
+            Err(e) => FulfillmentAttempt {
+                tx_hash,
+                nonce,
+                outcome: match e {
+                    RelayerError::MinedReverted(_) => AttemptOutcome::Reverted,
+                    RelayerError::Cancelled(_) | RelayerError::Dropped(_) => {
+                        AttemptOutcome::Dropped
+                    }
+                    _ => AttemptOutcome::Failed,
+                },
+                ..FulfillmentAttempt::failed(relayer, e.to_string())
+            },
+        }
+    }
+
+    /// Take over the confirmation of transactions left pending by a previous owner of
+    /// their key. Each account is held by one task that confirms its transactions in
+    /// nonce order, tracked and drained like the batches this replica sends.
+    async fn resume_orphans(
+        orphans: Vec<OrphanedBatch>,
+        relayer: &Relayer,
+        queue_db: &QueueDatabase,
+        in_flight: &mut JoinSet<BatchResult>,
+        in_flight_batches: &mut InFlightBatches,
+    ) {
+        let mut by_account: HashMap<Address, (Arc<RelayerAccount>, Vec<_>)> = HashMap::new();
+        for (account, sent, requests) in orphans {
+            by_account
+                .entry(account.address)
+                .or_insert_with(|| (account, Vec::new()))
+                .1
+                .push((sent, requests));
+        }
+
+        for (address, (account, mut batches)) in by_account {
+            batches.sort_by_key(|(sent, _)| sent.nonce);
+            let request_ids = batches
+                .iter()
+                .flat_map(|(_, requests)| requests.iter().map(|r| r.request_id))
+                .collect();
+
+            relayer.hold_account(address).await;
+            let queue_db = queue_db.clone();
+            let handle = in_flight.spawn(async move {
+                for (sent, requests) in batches {
+                    Self::resume_batch(sent, requests, &queue_db, &account).await?;
+                }
+                Ok(())
+            });
+            in_flight_batches.insert(handle.id(), (address, request_ids, handle));
+        }
+    }
+
+    /// Wait for a transaction broadcast by a previous owner of the key, bumping it while
+    /// stuck, and reconcile its requests
+    async fn resume_batch(
+        sent: SentBatch,
+        requests: Vec<PendingRequest>,
+        queue_db: &QueueDatabase,
+        account: &RelayerAccount,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        info!(
+            "Transaction {} is still pending, confirming its {} requests",
+            sent.tx_hash,
+            requests.len()
+        );
+
+        let result = account.confirm_batch(&sent).await;
+        let attempt = Self::confirmed_attempt(
+            account.address,
+            Some(sent.tx_hash),
+            Some(sent.nonce),
+            &result,
+        );
+        queue_db.record_attempts(&requests, &attempt).await?;
+
+        match result {
+            Ok(receipt) => Self::reconcile_batch(&requests, &receipt, queue_db, account).await,
+            // Nothing of ours was mined, the requests may still have been fulfilled elsewhere
+            Err(
+                RelayerError::MinedReverted(_)
+                | RelayerError::Cancelled(_)
+                | RelayerError::Dropped(_),
+            ) => {
+                Self::reconcile_requests(
+                    &requests,
+                    &HashSet::new(),
+                    sent.tx_hash,
+                    queue_db,
+                    account,
+                )
+                .await
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to confirm transaction {}, leaving its {} requests to the processing timeout: {}",
+                    sent.tx_hash,
+                    requests.len(),
+                    e
+                );
                 Ok(())
             }
         }
     }
+
+    /// Reconcile a mined batch against on-chain state.
+    ///
+    /// Requests with a `RandomnessFulfilled` log in the receipt are marked fulfilled.
+    /// The others are checked with `getRandomness`: requests fulfilled elsewhere are
+    /// marked fulfilled too, and the rest go back to the queue as failed attempts.
+    async fn reconcile_batch(
+        requests: &[PendingRequest],
+        receipt: &TransactionReceipt,
+        queue_db: &QueueDatabase,
+        account: &RelayerAccount,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let confirmed = oracle::fulfilled_request_ids(receipt.logs());
+        Self::reconcile_requests(
+            requests,
+            &confirmed,
+            receipt.transaction_hash,
+            queue_db,
+            account,
+        )
+        .await
+    }
+
+    /// Mark `confirmed` requests fulfilled and check the others with `getRandomness`
+    async fn reconcile_requests(
+        requests: &[PendingRequest],
+        confirmed: &HashSet<(Address, FixedBytes<32>)>,
+        tx_hash: TxHash,
+        queue_db: &QueueDatabase,
+        account: &RelayerAccount,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut fulfilled_requests = Vec::new();
+        let mut unfulfilled_requests = Vec::new();
+
+        for request in requests.iter() {
+            if confirmed.contains(&(request.contract_address, request.request_id)) {
+                fulfilled_requests.push(request.request_id);
+                continue;
+            }
+
+            let encoded_call = oracle::encode_get_randomness_call(request.request_id);
+            match account
+                .send_call(request.contract_address, encoded_call.abi_encode().into())
+                .await
+            {
+                Ok(call_result) => {
+                    match oracle::IVRFOracle::getRandomnessCall::abi_decode_returns(&call_result) {
+                        Ok(decoded_result) if decoded_result.fulfilled => {
+                            fulfilled_requests.push(request.request_id);
+                        }
+                        Ok(_) => {
+                            unfulfilled_requests.push(request.request_id);
+                        }
+                        Err(e) => {
+                            error!(
+                                "Failed to decode getRandomness result for request {}: {:?}",
+                                hex::encode(request.request_id),
+                                e
+                            );
+                            unfulfilled_requests.push(request.request_id);
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to check request {} on-chain: {:?}",
+                        hex::encode(request.request_id),
+                        e
+                    );
+                    unfulfilled_requests.push(request.request_id);
+                }
+            }
+        }
+
+        for request_id in fulfilled_requests.iter() {
+            queue_db.mark_fulfilled(*request_id).await?;
+        }
+
+        if !unfulfilled_requests.is_empty() {
+            crate::relayer::metrics::record_batch_unfulfilled(unfulfilled_requests.len());
+            let error_msg = format!("Not fulfilled on-chain after batch {tx_hash}");
+            queue_db
+                .mark_batch_failed(&unfulfilled_requests, &error_msg)
+                .await?;
+        }
+
+        info!(
+            "Batch processing complete: {} succeeded, {} failed/retrying. Used account {}",
+            fulfilled_requests.len(),
+            unfulfilled_requests.len(),
+            account.address
+        );
+
+        Ok(())
+    }
 }
 
 /// Create a PostgreSQL client using rindexer
@@ -255,3 +1235,109 @@ pub async fn create_postgres_client(
     let client = PostgresClient::new().await?;
     Ok(Arc::new(client))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{Bytes, U256};
+
+    fn request(index: u8) -> (PendingRequest, Call) {
+        let request = PendingRequest {
+            request_id: FixedBytes::repeat_byte(index),
+            contract_address: Address::ZERO,
+            requester: Address::ZERO,
+            block_hash: FixedBytes::ZERO,
+            status: "processing".to_string(),
+            retry_count: 1,
+            network: "anvil".to_string(),
+            queued_for: Duration::ZERO,
+            block_number: None,
+        };
+        let call = Call {
+            to: Address::ZERO,
+            value: U256::ZERO,
+            data: Bytes::from(vec![index]),
+        };
+        (request, call)
+    }
+
+    /// Gas estimate of 100_000 per call, as the index of each part's requests
+    async fn split(requests: u8, gas_budget: u64, estimate_fails: bool) -> Vec<Vec<u8>> {
+        let batch: Vec<_> = (0..requests).map(request).collect();
+        let batch_sizer = BatchSizer::new(100, gas_budget, None);
+        let batches =
+            QueueProcessor::split_to_gas_budget(batch, &batch_sizer, |calls| async move {
+                if estimate_fails {
+                    return Err(RelayerError::Rpc("unavailable".to_string()));
+                }
+                Ok(calls.len() as u64 * 100_000)
+            })
+            .await;
+
+        batches
+            .iter()
+            .map(|batch| batch.iter().map(|(_, call)| call.data[0]).collect())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn batches_within_the_budget_are_kept() {
+        assert_eq!(split(4, 400_000, false).await, vec![vec![0, 1, 2, 3]]);
+    }
+
+    #[tokio::test]
+    async fn oversized_batches_are_split_in_order() {
+        assert_eq!(split(4, 399_999, false).await, vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(
+            split(5, 200_000, false).await,
+            vec![vec![0, 1], vec![2], vec![3, 4]]
+        );
+    }
+
+    #[tokio::test]
+    async fn single_calls_are_sent_over_the_budget() {
+        assert_eq!(
+            split(3, 50_000, false).await,
+            vec![vec![0], vec![1], vec![2]]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_estimates_keep_the_batch() {
+        assert_eq!(split(3, 50_000, true).await, vec![vec![0, 1, 2]]);
+    }
+
+    #[tokio::test]
+    async fn single_mode_sends_one_transaction_per_request() {
+        let batch: Vec<_> = (0..3).map(request).collect();
+        let batch_sizer = BatchSizer::new(100, 30_000_000, None);
+        let batches =
+            QueueProcessor::plan_batches(batch, FulfillmentMode::Single, &batch_sizer, |_| async {
+                panic!("single transactions are not estimated as a batch")
+            })
+            .await;
+
+        let parts: Vec<Vec<u8>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(|(_, call)| call.data[0]).collect())
+            .collect();
+        assert_eq!(parts, vec![vec![0], vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn single_mode_takes_few_requests() {
+        let batch_sizer = BatchSizer::new(100, 30_000_000, None);
+        assert_eq!(
+            QueueProcessor::max_requests(FulfillmentMode::Single, &batch_sizer),
+            SINGLE_MODE_MAX_REQUESTS
+        );
+        assert_eq!(
+            QueueProcessor::max_requests(FulfillmentMode::Batch, &batch_sizer),
+            100
+        );
+        assert_eq!(
+            QueueProcessor::max_requests(FulfillmentMode::Multicall3(Address::ZERO), &batch_sizer),
+            100
+        );
+    }
+}
@@ -1,14 +1,27 @@
-use crate::oracle::Call;
+use super::gas::{self, FeeEstimate, GasStrategy};
+use super::nonce::{NonceDrift, NonceState, TrackedNonceManager};
+use super::rpc::RpcPool;
+use super::signer::AccountSigner;
+use super::{
+    Availability, FulfillmentMode, RelayerError, SkipReason, TxLifecycle, TxLifecycleConfig,
+};
+use crate::oracle::{decode_revert, Call};
 use alloy::primitives::Bytes;
 use alloy::{
-    network::{Ethereum, EthereumWallet},
-    primitives::{Address, U256},
+    consensus::Transaction as _,
+    eips::{
+        eip7702::{constants::EIP7702_DELEGATION_DESIGNATOR, Authorization},
+        BlockNumberOrTag,
+    },
+    network::{Ethereum, EthereumWallet, TransactionBuilder7702, TxSigner},
+    primitives::{Address, TxHash, U256},
     providers::{Provider, ProviderBuilder},
-    rpc::types::TransactionRequest,
-    signers::local::PrivateKeySigner,
+    rpc::types::{TransactionReceipt, TransactionRequest},
     sol,
     sol_types::SolCall,
+    transports::TransportError,
 };
+use std::ops::Range;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
@@ -21,15 +34,63 @@ sol! {
     }
 }
 
+/// How long an account rests after a failed transaction
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Most no-op transactions sent to fill a single nonce gap
+const MAX_GAP_FILL: usize = 16;
+
+/// A fulfillment transaction that was broadcast but may not be mined yet
+pub struct SentBatch {
+    pub tx_hash: TxHash,
+    pub nonce: u64,
+    // Rebroadcast with bumped fees while stuck
+    tx: TransactionRequest,
+}
+
+/// Where a transaction broadcast earlier, possibly by a previous run, stands
+pub enum BroadcastStatus {
+    Mined(Box<TransactionReceipt>),
+    /// Still in the mempool, with what `confirm_batch` needs to take over
+    Pending(Box<SentBatch>),
+    /// Not mined and no longer known, or its nonce was used by another transaction
+    Dropped,
+}
+
+/// Counters and nonce tracking of an account
+#[derive(Debug, Clone)]
+pub struct AccountMetrics {
+    pub total_transactions: u64,
+    pub total_failures: u64,
+    pub nonce: NonceState,
+}
+
 /// Represents a single relayer account with its own provider
 pub struct RelayerAccount {
     pub address: Address,
     pub min_gas_balance: U256,
     pub bebe_address: Option<Address>,
+    pub fulfillment_mode: FulfillmentMode,
+
+    // Kept to sign EIP-7702 authorizations
+    signer: AccountSigner,
 
     // Alloy provider with automatic nonce management
     provider: Arc<dyn Provider<Ethereum> + Send + Sync>,
 
+    // Endpoints behind the provider, for quorum reads
+    rpc: Arc<RpcPool>,
+
+    // Replaces stuck transactions until they are mined
+    lifecycle: TxLifecycle,
+
+    // Nonce cache shared with the provider's nonce filler
+    nonce_manager: TrackedNonceManager,
+
+    // Fee pricing, clamped to the fee ceiling
+    gas_strategy: Arc<dyn GasStrategy>,
+    max_fee_per_gas: u128,
+
     // Track account state
     state: Arc<Mutex<AccountState>>,
 }
@@ -46,24 +107,24 @@ struct AccountState {
 
 impl RelayerAccount {
     pub async fn new(
-        private_key: &str,
-        rpc_url: &str,
+        signer: AccountSigner,
+        rpc: Arc<RpcPool>,
         min_gas_balance: U256,
         bebe_address: Option<Address>,
+        fulfillment_mode: FulfillmentMode,
+        lifecycle_config: TxLifecycleConfig,
+        gas_strategy: Arc<dyn GasStrategy>,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        // Parse private key and create wallet
-        let signer: PrivateKeySigner = private_key
-            .parse()
-            .map_err(|e| format!("Failed to parse private key: {e}"))?;
         let address = signer.address();
-        let wallet = EthereumWallet::from(signer);
+        let wallet = EthereumWallet::from(signer.clone());
 
         // Create provider with automatic nonce management
+        let nonce_manager = TrackedNonceManager::default();
         let provider: Arc<dyn Provider<Ethereum> + Send + Sync> = Arc::new(
             ProviderBuilder::new()
-                .with_cached_nonce_management()
+                .with_nonce_management(nonce_manager.clone())
                 .wallet(wallet)
-                .connect_http(rpc_url.parse()?),
+                .connect_client(rpc.client()),
         );
 
         // Initialize state
@@ -80,7 +141,14 @@ impl RelayerAccount {
             address,
             min_gas_balance,
             bebe_address,
+            fulfillment_mode,
+            signer,
             provider: provider.clone(),
+            rpc,
+            max_fee_per_gas: lifecycle_config.max_fee_per_gas,
+            lifecycle: TxLifecycle::new(lifecycle_config),
+            nonce_manager,
+            gas_strategy,
             state,
         };
 
@@ -90,18 +158,18 @@ impl RelayerAccount {
         Ok(account)
     }
 
-    /// Check if this account is available for use
-    pub async fn is_available(
-        &self,
-        pending_block_threshold: u64,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    /// Check if this account is available for use, or why it is not
+    pub async fn is_available(&self, pending_block_threshold: u64) -> Availability {
         let mut state = self.state.lock().await;
 
-        // Check if recently failed (cooldown period of 30 seconds)
+        // Check if recently failed
         if let Some(last_failure) = state.last_failure {
-            if last_failure.elapsed() < Duration::from_secs(30) {
+            let elapsed = last_failure.elapsed();
+            if elapsed < FAILURE_COOLDOWN {
                 debug!("Account {} is in failure cooldown", self.address);
-                return Ok(false);
+                return Availability::Skipped(SkipReason::RecentFailure {
+                    cooldown_remaining: FAILURE_COOLDOWN - elapsed,
+                });
             } else {
                 // Clear the failure flag after cooldown
                 state.last_failure = None;
@@ -114,7 +182,10 @@ impl RelayerAccount {
                 "Account {} has too many pending transactions: {}",
                 self.address, state.pending_tx_count
             );
-            return Ok(false);
+            return Availability::Skipped(SkipReason::PendingTransaction {
+                pending: state.pending_tx_count,
+                threshold: pending_block_threshold,
+            });
         }
 
         // Check balance (with caching to avoid too many RPC calls)
@@ -125,7 +196,9 @@ impl RelayerAccount {
 
         if should_check_balance {
             drop(state); // Release lock before RPC call
-            self.update_balance().await?;
+            if let Err(e) = self.update_balance().await {
+                return Availability::Skipped(SkipReason::RpcError(e.to_string()));
+            }
             state = self.state.lock().await;
         }
 
@@ -134,15 +207,43 @@ impl RelayerAccount {
                 "Account {} has insufficient balance: {} < {}",
                 self.address, state.cached_balance, self.min_gas_balance
             );
-            return Ok(false);
+            return Availability::Skipped(SkipReason::InsufficientGas {
+                balance: state.cached_balance,
+                min_balance: self.min_gas_balance,
+            });
         }
 
-        Ok(true)
+        Availability::Available
+    }
+
+    /// Number of sent transactions still waiting for a receipt
+    pub async fn pending_tx_count(&self) -> usize {
+        self.state.lock().await.pending_tx_count
+    }
+
+    /// Cached balance above `min_gas_balance`
+    pub async fn gas_headroom(&self) -> U256 {
+        self.state
+            .lock()
+            .await
+            .cached_balance
+            .saturating_sub(self.min_gas_balance)
+    }
+
+    /// Balance at the last refresh
+    pub async fn cached_balance(&self) -> U256 {
+        self.state.lock().await.cached_balance
     }
 
     /// Update the cached balance
-    async fn update_balance(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let balance = self.provider.get_balance(self.address).await?;
+    pub async fn update_balance(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let address = self.address;
+        let balance = self
+            .rpc
+            .quorum("balance", |provider, block| async move {
+                provider.get_balance(address).block_id(block).await
+            })
+            .await?;
 
         let mut state = self.state.lock().await;
         state.cached_balance = balance;
@@ -195,9 +296,181 @@ impl RelayerAccount {
     }
 
     /// Get account metrics
-    pub async fn get_metrics(&self) -> (u64, u64) {
-        let state = self.state.lock().await;
-        (state.total_transactions, state.total_failures)
+    pub async fn get_metrics(&self) -> AccountMetrics {
+        let (total_transactions, total_failures) = {
+            let state = self.state.lock().await;
+            (state.total_transactions, state.total_failures)
+        };
+
+        AccountMetrics {
+            total_transactions,
+            total_failures,
+            nonce: self.nonce_manager.state().await,
+        }
+    }
+
+    /// Compare the cached nonce with the chain's pending nonce and repair drift.
+    ///
+    /// A cache behind the chain, or ahead of it with nothing in flight, is resynchronised.
+    /// A gap below an in-flight transaction that is still there at the next check is
+    /// filled with no-op transactions.
+    pub async fn sync_nonce(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let chain_pending = self
+            .provider
+            .get_transaction_count(self.address)
+            .pending()
+            .await?;
+
+        match self.nonce_manager.drift(chain_pending).await {
+            NonceDrift::InSync => {}
+            NonceDrift::Behind {
+                next,
+                chain_pending,
+            } => {
+                warn!(
+                    "Nonce of {} is behind the chain ({} < {}), resyncing",
+                    self.address, next, chain_pending
+                );
+                self.nonce_manager.resync(chain_pending).await;
+            }
+            NonceDrift::Ahead {
+                next,
+                chain_pending,
+            } => {
+                let in_flight = self.state.lock().await.pending_tx_count > 0;
+                if !in_flight {
+                    warn!(
+                        "Nonce of {} is ahead of the chain ({} > {}), resyncing",
+                        self.address, next, chain_pending
+                    );
+                    self.nonce_manager.resync(chain_pending).await;
+                } else if self.nonce_manager.confirm_gap(next, chain_pending).await {
+                    // The in-flight transaction holds the last nonce handed out
+                    self.fill_nonce_gap(chain_pending..next - 1).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send zero-value self-transfers for the missing nonces
+    async fn fill_nonce_gap(&self, nonces: Range<u64>) {
+        warn!(
+            "Filling nonce gap {}..{} of {}",
+            nonces.start, nonces.end, self.address
+        );
+
+        let fees = match self.estimate_fees(Duration::ZERO).await {
+            Ok(fees) => fees,
+            Err(e) => {
+                warn!("Failed to price the nonce gap fill: {}", e);
+                return;
+            }
+        };
+
+        let mut filled = 0;
+        for nonce in nonces.take(MAX_GAP_FILL) {
+            let tx = TransactionRequest::default()
+                .from(self.address)
+                .to(self.address)
+                .value(U256::ZERO)
+                .nonce(nonce)
+                .max_fee_per_gas(fees.max_fee_per_gas)
+                .max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+
+            match self.provider.send_transaction(tx).await {
+                Ok(pending) => {
+                    debug!("Filled nonce {} with {}", nonce, pending.tx_hash());
+                    filled += 1;
+                }
+                Err(e) => warn!("Failed to fill nonce {}: {}", nonce, e),
+            }
+        }
+
+        self.nonce_manager.record_gaps_filled(filled).await;
+    }
+
+    /// Whether the account's code is the EIP-7702 designator delegating to `bebe`
+    pub async fn is_delegated_to(
+        &self,
+        bebe: Address,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let code = self.provider.get_code_at(self.address).await?;
+        let mut expected = EIP7702_DELEGATION_DESIGNATOR.to_vec();
+        expected.extend_from_slice(bebe.as_slice());
+
+        Ok(code.as_ref() == expected.as_slice())
+    }
+
+    /// Delegate the account to BEBE with an EIP-7702 authorization unless it already is.
+    ///
+    /// The authorization is carried by a type-4 transaction sent by the account itself,
+    /// so it is signed for the nonce after the transaction's own.
+    pub async fn ensure_delegation(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (FulfillmentMode::Batch, Some(bebe)) = (self.fulfillment_mode, self.bebe_address)
+        else {
+            return Ok(());
+        };
+
+        if self.is_delegated_to(bebe).await? {
+            debug!("Account {} is already delegated to {}", self.address, bebe);
+            return Ok(());
+        }
+
+        info!("Delegating account {} to BEBE at {}", self.address, bebe);
+
+        let chain_id = self.provider.get_chain_id().await?;
+        let nonce = self
+            .provider
+            .get_transaction_count(self.address)
+            .pending()
+            .await?;
+
+        let authorization = Authorization {
+            chain_id: U256::from(chain_id),
+            address: bebe,
+            nonce: nonce + 1,
+        };
+        let authorization = self.signer.sign_authorization(authorization).await?;
+
+        let tx = TransactionRequest::default()
+            .from(self.address)
+            .to(self.address)
+            .value(U256::ZERO)
+            .nonce(nonce)
+            .with_authorization_list(vec![authorization]);
+        let gas_limit = self.provider.estimate_gas(tx.clone()).await?;
+        let fees = self.estimate_fees(Duration::ZERO).await?;
+        let tx = tx
+            .gas_limit(gas_limit)
+            .max_fee_per_gas(fees.max_fee_per_gas)
+            .max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+
+        // The nonce is set by hand, so the filler's cache has to skip it
+        self.nonce_manager.reserve(nonce).await;
+
+        let pending_tx = self.provider.send_transaction(tx.clone()).await?;
+        let tx_hash = *pending_tx.tx_hash();
+        info!(
+            "Sent EIP-7702 authorization {} for account {}",
+            tx_hash, self.address
+        );
+
+        let receipt = self
+            .lifecycle
+            .wait_for_receipt(self.provider.as_ref(), self.address, &tx, tx_hash)
+            .await?;
+        if !receipt.status() || !self.is_delegated_to(bebe).await? {
+            return Err(format!(
+                "EIP-7702 authorization {} did not delegate {} to {}",
+                receipt.transaction_hash, self.address, bebe
+            )
+            .into());
+        }
+
+        info!("Account {} delegated to BEBE at {}", self.address, bebe);
+        Ok(())
     }
 
     pub async fn send_call(
@@ -207,68 +480,282 @@ impl RelayerAccount {
     ) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
         let tx = TransactionRequest::default().to(to).input(data.into());
 
-        let call_result = self.provider.call(tx).await?;
+        let call_result = self
+            .rpc
+            .quorum("eth_call", |provider, block| {
+                let tx = tx.clone();
+                async move { provider.call(tx).block(block).await }
+            })
+            .await?;
 
         Ok(call_result)
     }
 
-    /// Send a batch of calls through BEBE (ERC7821)
-    pub async fn send_batch(
+    /// Gas limit of the latest block
+    pub async fn block_gas_limit(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let block = self
+            .provider
+            .get_block_by_number(BlockNumberOrTag::Latest)
+            .await?
+            .ok_or("Latest block not found")?;
+
+        Ok(block.header.gas_limit)
+    }
+
+    /// Estimate the gas of the transaction fulfilling `calls` in the account's mode
+    pub async fn estimate_batch_gas(&self, calls: &[Call]) -> Result<u64, RelayerError> {
+        self.estimate_gas(self.fulfillment_transaction(calls)?)
+            .await
+    }
+
+    /// Simulate a batch with `eth_call` and return its revert data if it reverts
+    pub async fn simulate_batch(&self, calls: &[Call]) -> Result<Option<Bytes>, RelayerError> {
+        // Multicall3 replaces the oracle's error with its own, a lone call is simulated
+        // as sent by the aggregator to get the oracle's error back
+        if let (FulfillmentMode::Multicall3(multicall3), [call]) = (self.fulfillment_mode, calls) {
+            return self.simulate(single_transaction(multicall3, call)).await;
+        }
+        self.simulate(self.fulfillment_transaction(calls)?).await
+    }
+
+    async fn estimate_gas(&self, tx: TransactionRequest) -> Result<u64, RelayerError> {
+        self.provider
+            .estimate_gas(tx)
+            .await
+            .map_err(|e| match revert_data(&e) {
+                Some(revert_data) => RelayerError::Reverted(decode_revert(&revert_data)),
+                None => RelayerError::GasEstimation(e.to_string()),
+            })
+    }
+
+    async fn simulate(&self, tx: TransactionRequest) -> Result<Option<Bytes>, RelayerError> {
+        match self.provider.call(tx).await {
+            Ok(_) => Ok(None),
+            Err(e) => match revert_data(&e) {
+                Some(revert_data) => Ok(Some(revert_data)),
+                None => Err(RelayerError::Rpc(e.to_string())),
+            },
+        }
+    }
+
+    /// Broadcast `calls` in the account's fulfillment mode.
+    ///
+    /// The exact payload is simulated and gas-estimated first, so a transaction that
+    /// would revert is never broadcast. In single mode `calls` must hold one call.
+    pub async fn broadcast_batch(
         &self,
         calls: &[Call],
-    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        // Encode the batch for ERC7821
-        let batch_data = crate::oracle::encode_batch_for_erc7821(calls);
+        queue_age: Duration,
+    ) -> Result<SentBatch, RelayerError> {
+        let tx = self.fulfillment_transaction(calls)?;
 
-        // Build transaction to send to the account's own address (EIP-7702 delegation)
-        let tx = TransactionRequest::default()
-            .to(self.address)
-            .input(batch_data.abi_encode().into());
+        // Pre-flight: doomed transactions must not cost gas
+        if let Some(revert_data) = self.simulate(tx.clone()).await? {
+            let failure = decode_revert(&revert_data);
+            warn!(
+                "Fulfillment of {} calls from {} reverts in simulation: {}",
+                calls.len(),
+                self.address,
+                failure
+            );
+            return Err(RelayerError::Reverted(failure));
+        }
+        let gas_limit = self.estimate_gas(tx.clone()).await?;
+
+        let fees = self.estimate_fees(queue_age).await?;
+
+        // The nonce is taken here rather than by the filler, so it is known for sure
+        let nonce = self
+            .nonce_manager
+            .next_nonce(self.provider.as_ref(), self.address)
+            .await
+            .map_err(|e| RelayerError::Rpc(e.to_string()))?;
+
+        let tx = tx
+            .nonce(nonce)
+            .gas_limit(gas_limit)
+            .max_fee_per_gas(fees.max_fee_per_gas)
+            .max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
 
         // Mark transaction as being sent
         self.mark_transaction_sent().await;
 
-        // Send transaction - Alloy handles nonce automatically
-        let pending_tx = match self.provider.send_transaction(tx).await {
+        let pending_tx = match self.provider.send_transaction(tx.clone()).await {
             Ok(tx) => tx,
             Err(e) => {
                 self.mark_transaction_failed().await;
                 error!("Failed to send batch transaction: {:?}", e);
-                return Err(format!("Failed to send transaction: {e}").into());
+
+                // The nonce was consumed but never reached the chain
+                if let Err(e) = self.sync_nonce().await {
+                    warn!("Failed to check nonce of {}: {}", self.address, e);
+                }
+                return Err(RelayerError::Send(e.to_string()));
             }
         };
 
-        let tx_hash = pending_tx.tx_hash().to_string();
+        let tx_hash = *pending_tx.tx_hash();
 
         info!(
-            "Sent batch transaction {} with {} calls from account {}",
+            "Sent fulfillment transaction {} with {} calls from account {}",
             tx_hash,
             calls.len(),
             self.address
         );
 
-        // Wait for confirmation
-        match pending_tx.get_receipt().await {
+        Ok(SentBatch { tx_hash, nonce, tx })
+    }
+
+    /// Look up a transaction broadcast with `nonce` that may have been replaced since
+    pub async fn broadcast_status(
+        &self,
+        tx_hash: TxHash,
+        nonce: Option<u64>,
+    ) -> Result<BroadcastStatus, RelayerError> {
+        let rpc_error = |e: TransportError| RelayerError::Receipt {
+            tx_hash,
+            error: e.to_string(),
+        };
+
+        if let Some(receipt) = self
+            .provider
+            .get_transaction_receipt(tx_hash)
+            .await
+            .map_err(rpc_error)?
+        {
+            return Ok(BroadcastStatus::Mined(Box::new(receipt)));
+        }
+
+        // A replacement or cancellation with the same nonce was mined instead
+        if let Some(nonce) = nonce {
+            let mined_nonce = self
+                .provider
+                .get_transaction_count(self.address)
+                .latest()
+                .await
+                .map_err(rpc_error)?;
+            if mined_nonce > nonce {
+                return Ok(BroadcastStatus::Dropped);
+            }
+        }
+
+        match self
+            .provider
+            .get_transaction_by_hash(tx_hash)
+            .await
+            .map_err(rpc_error)?
+        {
+            Some(sent) => Ok(BroadcastStatus::Pending(Box::new(SentBatch {
+                tx_hash,
+                nonce: sent.nonce(),
+                tx: sent.into_request().from(self.address),
+            }))),
+            None => Ok(BroadcastStatus::Dropped),
+        }
+    }
+
+    /// Wait until a broadcast batch is mined, replacing it while it is stuck
+    pub async fn confirm_batch(
+        &self,
+        sent: &SentBatch,
+    ) -> Result<TransactionReceipt, RelayerError> {
+        let tx_hash = sent.tx_hash;
+        match self
+            .lifecycle
+            .wait_for_receipt(self.provider.as_ref(), self.address, &sent.tx, tx_hash)
+            .await
+        {
             Ok(receipt) => {
                 if receipt.status() {
                     self.mark_transaction_confirmed().await;
-                    Ok(tx_hash)
+                    Ok(receipt)
                 } else {
                     self.mark_transaction_failed().await;
-                    Err("Batch transaction failed".into())
+                    Err(RelayerError::MinedReverted(tx_hash))
                 }
             }
             Err(e) => {
                 self.mark_transaction_failed().await;
                 error!("Failed to get receipt: {:?}", e);
-                Err(format!("Failed to get receipt: {e}").into())
+                Err(e)
             }
         }
     }
+
+    /// Fees from the gas strategy, clamped to the fee ceiling
+    async fn estimate_fees(&self, queue_age: Duration) -> Result<FeeEstimate, RelayerError> {
+        gas::capped_estimate(
+            self.gas_strategy.as_ref(),
+            self.provider.as_ref(),
+            queue_age,
+            self.max_fee_per_gas,
+        )
+        .await
+        .map_err(|e| RelayerError::Rpc(e.to_string()))
+    }
+
+    /// Transaction fulfilling `calls` in the account's mode
+    fn fulfillment_transaction(&self, calls: &[Call]) -> Result<TransactionRequest, RelayerError> {
+        match (self.fulfillment_mode, calls) {
+            // Sent to the account's own address (EIP-7702 delegation)
+            (FulfillmentMode::Batch, _) => Ok(erc7821_transaction(self.address, calls)),
+            (FulfillmentMode::Single, [call]) => Ok(single_transaction(self.address, call)),
+            (FulfillmentMode::Single, _) => Err(RelayerError::Send(format!(
+                "single fulfillment mode sends one call per transaction, got {}",
+                calls.len()
+            ))),
+            (FulfillmentMode::Multicall3(multicall3), _) => {
+                Ok(multicall3_transaction(self.address, multicall3, calls))
+            }
+        }
+    }
+}
+
+/// Batch transaction from the account to itself (EIP-7702 delegation to BEBE)
+fn erc7821_transaction(from: Address, calls: &[Call]) -> TransactionRequest {
+    let batch_data = crate::oracle::encode_batch_for_erc7821(calls);
+    TransactionRequest::default()
+        .from(from)
+        .to(from)
+        .input(batch_data.abi_encode().into())
+}
+
+/// Plain transaction to the oracle contract
+fn single_transaction(from: Address, call: &Call) -> TransactionRequest {
+    TransactionRequest::default()
+        .from(from)
+        .to(call.to)
+        .value(call.value)
+        .input(call.data.clone().into())
+}
+
+/// `aggregate3` transaction to a Multicall3 contract
+fn multicall3_transaction(
+    from: Address,
+    multicall3: Address,
+    calls: &[Call],
+) -> TransactionRequest {
+    let batch_data = crate::oracle::encode_batch_for_multicall3(calls);
+    TransactionRequest::default()
+        .from(from)
+        .to(multicall3)
+        .input(batch_data.abi_encode().into())
+}
+
+/// Revert data of a failed `eth_call` or `eth_estimateGas`, if the call reverted.
+///
+/// BEBE bubbles up the revert data of the failing call.
+pub(super) fn revert_data(error: &TransportError) -> Option<Bytes> {
+    let payload = error.as_error_resp()?;
+    match payload.as_revert_data() {
+        Some(revert_data) => Some(revert_data),
+        None if payload.message.contains("revert") => Some(Bytes::new()),
+        None => None,
+    }
 }
 
 /// Format Wei as ETH for logging
-fn format_ether(wei: U256) -> String {
+pub(super) fn format_ether(wei: U256) -> String {
     let eth = wei / U256::from(10).pow(U256::from(18));
     let remainder = wei % U256::from(10).pow(U256::from(18));
     let decimal = remainder / U256::from(10).pow(U256::from(14)); // 4 decimal places
@@ -0,0 +1,132 @@
+//! Batch sizing from a gas budget and the gas used by recent batches.
+
+use std::sync::Mutex;
+
+/// Gas of a batch transaction outside its calls (intrinsic cost and `execute` overhead)
+const BATCH_BASE_GAS: u64 = 50_000;
+
+/// Per-call gas assumed until the first receipt is seen
+const DEFAULT_GAS_PER_CALL: u64 = 80_000;
+
+/// Weight of the latest receipt in the per-call moving average
+const GAS_PER_CALL_SMOOTHING: f64 = 0.2;
+
+pub struct BatchSizer {
+    max_batch_size: usize,
+    gas_budget: u64,
+    gas_per_call: Mutex<f64>,
+}
+
+impl BatchSizer {
+    /// Plan batches of up to `max_batch_size` calls within `gas_budget`, never larger
+    /// than a block when its gas limit is known
+    pub fn new(max_batch_size: usize, gas_budget: u64, block_gas_limit: Option<u64>) -> Self {
+        Self {
+            max_batch_size: max_batch_size.max(1),
+            gas_budget: block_gas_limit.map_or(gas_budget, |limit| gas_budget.min(limit)),
+            gas_per_call: Mutex::new(DEFAULT_GAS_PER_CALL as f64),
+        }
+    }
+
+    pub const fn gas_budget(&self) -> u64 {
+        self.gas_budget
+    }
+
+    /// Current per-call gas estimate
+    pub fn gas_per_call(&self) -> u64 {
+        self.gas_per_call
+            .lock()
+            .map(|g| *g as u64)
+            .unwrap_or(DEFAULT_GAS_PER_CALL)
+    }
+
+    /// Number of calls expected to fit the gas budget, capped by `BATCH_SIZE`
+    pub fn max_calls(&self) -> usize {
+        let available = self.gas_budget.saturating_sub(BATCH_BASE_GAS);
+        let calls = available / self.gas_per_call().max(1);
+        (calls as usize).clamp(1, self.max_batch_size)
+    }
+
+    /// Whether a batch with this gas estimate can be sent as is
+    pub const fn fits(&self, estimated_gas: u64) -> bool {
+        estimated_gas <= self.gas_budget
+    }
+
+    /// Update the per-call estimate from a mined batch
+    pub fn record_receipt(&self, calls: usize, gas_used: u64) {
+        if calls == 0 {
+            return;
+        }
+
+        let observed = gas_used.saturating_sub(BATCH_BASE_GAS) as f64 / calls as f64;
+        if let Ok(mut gas_per_call) = self.gas_per_call.lock() {
+            *gas_per_call += GAS_PER_CALL_SMOOTHING * (observed - *gas_per_call);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_is_capped_by_the_block() {
+        assert_eq!(BatchSizer::new(10, 5_000_000, None).gas_budget(), 5_000_000);
+        assert_eq!(
+            BatchSizer::new(10, 5_000_000, Some(30_000_000)).gas_budget(),
+            5_000_000
+        );
+        assert_eq!(
+            BatchSizer::new(10, 50_000_000, Some(30_000_000)).gas_budget(),
+            30_000_000
+        );
+    }
+
+    #[test]
+    fn max_calls_fits_the_budget() {
+        // (1_000_000 - 50_000) / 80_000 calls with the default estimate
+        let sizer = BatchSizer::new(100, 1_000_000, None);
+        assert_eq!(sizer.max_calls(), 11);
+
+        // Capped by the batch size
+        let sizer = BatchSizer::new(5, 1_000_000, None);
+        assert_eq!(sizer.max_calls(), 5);
+
+        // At least one call, even when none fits
+        let sizer = BatchSizer::new(100, 40_000, None);
+        assert_eq!(sizer.max_calls(), 1);
+        assert_eq!(BatchSizer::new(0, 1_000_000, None).max_calls(), 1);
+    }
+
+    #[test]
+    fn receipts_move_the_estimate() {
+        let sizer = BatchSizer::new(100, 1_000_000, None);
+        assert_eq!(sizer.gas_per_call(), DEFAULT_GAS_PER_CALL);
+
+        // 10 calls at 30_000 each pull the average a fifth of the way down
+        sizer.record_receipt(10, BATCH_BASE_GAS + 10 * 30_000);
+        assert_eq!(sizer.gas_per_call(), 70_000);
+        assert_eq!(sizer.max_calls(), 13);
+
+        // Repeated receipts converge to the observed gas
+        for _ in 0..100 {
+            sizer.record_receipt(10, BATCH_BASE_GAS + 10 * 30_000);
+        }
+        assert!(sizer.gas_per_call().abs_diff(30_000) <= 1);
+        assert_eq!(sizer.max_calls(), 31);
+    }
+
+    #[test]
+    fn empty_receipts_are_ignored() {
+        let sizer = BatchSizer::new(100, 1_000_000, None);
+        sizer.record_receipt(0, 1_000_000);
+        assert_eq!(sizer.gas_per_call(), DEFAULT_GAS_PER_CALL);
+    }
+
+    #[test]
+    fn fits_up_to_the_budget() {
+        let sizer = BatchSizer::new(100, 1_000_000, None);
+        assert!(sizer.fits(1_000_000));
+        assert!(!sizer.fits(1_000_001));
+    }
+}
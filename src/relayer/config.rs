@@ -1,27 +1,79 @@
-use alloy::primitives::U256;
+use super::keys::{self, KeySource};
+use super::signer::RemoteSignerProtocol;
+use alloy::primitives::{address, Address, U256};
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+// Holds key material, so it is neither serializable nor printed in full
+#[derive(Debug, Clone)]
 pub struct RelayerConfig {
     pub accounts: Vec<AccountConfig>,
+    pub rpc: RpcConfig,
+    pub remote_signer: Option<RemoteSignerConfig>,
     pub scheduler: SchedulerType,
     pub pending_block_threshold: u64,
     pub bebe_address: Option<String>,
+    pub fulfillment_mode: FulfillmentMode,
     pub batch_size: usize,
+    pub batch_gas_budget: u64,
+    pub isolate_failures: bool,
+    /// Blocks on top of a request's block before it is fulfilled
+    pub confirmation_depth: u64,
+    pub stuck_tx_blocks: u64,
+    pub fee_bump_percent: u64,
+    /// Blocks a sent transaction is tracked before it is given up as dropped
+    pub tx_timeout_blocks: u64,
+    pub max_fee_per_gas_wei: u128,
+    pub gas_strategy: GasStrategyType,
+    pub treasury: Option<TreasuryConfig>,
+    pub randomness_source: RandomnessSourceType,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone)]
 pub struct AccountConfig {
-    pub private_key: String,
+    pub key: KeySource,
+    pub min_gas_wei: String,
+}
+
+/// RPC endpoints, in order of preference
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    pub urls: Vec<String>,
+    /// Endpoints raced for each read
+    pub active_transports: usize,
+    /// Endpoints that must agree on a critical read
+    pub quorum: usize,
+}
+
+/// Remote signer holding relayer keys, one account per listed key
+#[derive(Debug, Clone)]
+pub struct RemoteSignerConfig {
+    pub url: String,
+    pub protocol: RemoteSignerProtocol,
     pub min_gas_wei: String,
 }
 
+/// Treasury account topping up relayer accounts
+#[derive(Debug, Clone)]
+pub struct TreasuryConfig {
+    pub key: KeySource,
+    /// Top up accounts whose balance falls below this
+    pub low_water_wei: u128,
+    /// Balance a top-up brings an account back to
+    pub high_water_wei: u128,
+    /// Most the treasury sends over any 24 hours
+    pub daily_cap_wei: u128,
+    pub check_interval_secs: u64,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum SchedulerType {
     RoundRobin,
     Random,
+    LeastPending,
+    BalanceWeighted,
 }
 
 impl Default for SchedulerType {
@@ -30,26 +82,85 @@ impl Default for SchedulerType {
     }
 }
 
+/// Canonical Multicall3 deployment, at the same address on most chains
+const MULTICALL3_ADDRESS: Address = address!("0xcA11bde05977b3631167028862bE2a173976CA11");
+
+/// How fulfillment calls are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FulfillmentMode {
+    /// ERC-7821 batches executed by the account itself through its BEBE delegation
+    Batch,
+    /// One `fulfillRandomness` transaction per request
+    Single,
+    /// Batches sent through a Multicall3 `aggregate3` contract
+    Multicall3(Address),
+}
+
+/// EIP-1559 fee pricing for relayer transactions
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GasStrategyType {
+    #[default]
+    Provider,
+    Fixed,
+    FeeHistory,
+    Urgency,
+}
+
+/// Entropy strategy used to fulfill requests
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RandomnessSourceType {
+    #[default]
+    Vrf,
+    Os,
+    ChaCha,
+    HmacDrbg,
+}
+
 impl RelayerConfig {
     /// Load configuration from environment variables
     /// Expected format:
-    /// RELAYER_PRIVATE_KEYS=0xkey1,0xkey2,0xkey3
+    /// RPC_URLS=http://node1:8545,http://node2:8545 (or RPC_URL=http://127.0.0.1:8545)
+    /// RPC_ACTIVE_TRANSPORTS=2
+    /// RPC_QUORUM=1
+    /// RELAYER_PRIVATE_KEYS=0xkey1,0xkey2,0xkey3 (or RELAYER_PRIVATE_KEYS_FILE=/run/secrets/keys)
+    /// RELAYER_KEYSTORES=/keys/relayer1.json,/keys/relayer2.json
+    /// RELAYER_KEYSTORE_PASSWORD_FILE=/run/secrets/password (prompted for when unset)
+    /// RELAYER_REMOTE_SIGNER_URL=http://127.0.0.1:9000
+    /// RELAYER_REMOTE_SIGNER_PROTOCOL=web3signer
     /// RELAYER_MIN_GAS_WEI=50000000000000000
     /// RELAYER_SCHEDULER=round_robin
     /// RELAYER_PENDING_BLOCK_THRESHOLD=3
+    /// FULFILLMENT_MODE=batch
+    /// MULTICALL3_ADDRESS=0xcA11bde05977b3631167028862bE2a173976CA11
+    /// BATCH_GAS_BUDGET=15000000
+    /// BATCH_ISOLATE_FAILURES=true
+    /// RELAYER_STUCK_TX_BLOCKS=3
+    /// RELAYER_FEE_BUMP_PERCENT=15
+    /// RELAYER_TX_TIMEOUT_BLOCKS=50
+    /// RELAYER_MAX_FEE_PER_GAS_WEI=500000000000
+    /// RELAYER_GAS_STRATEGY=provider
+    /// TREASURY_PRIVATE_KEY=0xkey (or TREASURY_PRIVATE_KEY_FILE, or TREASURY_KEYSTORE with
+    /// TREASURY_KEYSTORE_PASSWORD_FILE)
+    /// TREASURY_LOW_WATER_WEI=10000000000000000
+    /// TREASURY_HIGH_WATER_WEI=50000000000000000
+    /// TREASURY_DAILY_CAP_WEI=1000000000000000000
+    /// TREASURY_CHECK_INTERVAL_SECS=60
+    /// RANDOMNESS_SOURCE=vrf
     pub fn from_env() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        // Parse private keys - RELAYER_PRIVATE_KEYS is required
-        let private_keys_str = env::var("RELAYER_PRIVATE_KEYS")
-            .map_err(|_| "RELAYER_PRIVATE_KEYS environment variable is not set")?;
-
-        let private_keys: Vec<String> = private_keys_str
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
+        // Parse relayer keys - raw private keys and/or keystores are required
+        let mut relayer_keys = match keys::secret_env("RELAYER_PRIVATE_KEYS")? {
+            Some(private_keys) => keys::parse_private_keys(&private_keys),
+            None => Vec::new(),
+        };
 
-        if private_keys.is_empty() {
-            return Err("No private keys found in RELAYER_PRIVATE_KEYS".into());
+        if let Ok(keystore_paths) = env::var("RELAYER_KEYSTORES") {
+            let password_file = env::var("RELAYER_KEYSTORE_PASSWORD_FILE")
+                .ok()
+                .map(PathBuf::from);
+            relayer_keys.extend(keys::keystores(&keystore_paths, password_file.as_deref())?);
         }
 
         // Parse min gas (use same value for all accounts)
@@ -60,11 +171,44 @@ impl RelayerConfig {
         let _ = U256::from_str_radix(&min_gas_wei, 10)
             .map_err(|_| "Invalid RELAYER_MIN_GAS_WEI value")?;
 
+        // Parse remote signer, whose keys are listed at startup
+        let remote_signer = match env::var("RELAYER_REMOTE_SIGNER_URL") {
+            Ok(url) => {
+                let protocol_str = env::var("RELAYER_REMOTE_SIGNER_PROTOCOL")
+                    .unwrap_or_else(|_| "web3signer".to_string());
+
+                let protocol = match protocol_str.to_lowercase().as_str() {
+                    "web3signer" => RemoteSignerProtocol::Web3Signer,
+                    "json_rpc" => RemoteSignerProtocol::JsonRpc,
+                    _ => {
+                        return Err(format!(
+                            "Invalid RELAYER_REMOTE_SIGNER_PROTOCOL value: {protocol_str}. Must be one of: web3signer, json_rpc"
+                        )
+                        .into());
+                    }
+                };
+
+                Some(RemoteSignerConfig {
+                    url,
+                    protocol,
+                    min_gas_wei: min_gas_wei.clone(),
+                })
+            }
+            Err(_) => None,
+        };
+
+        if relayer_keys.is_empty() && remote_signer.is_none() {
+            return Err(
+                "No relayer keys found in RELAYER_PRIVATE_KEYS, RELAYER_PRIVATE_KEYS_FILE, RELAYER_KEYSTORES or RELAYER_REMOTE_SIGNER_URL"
+                    .into(),
+            );
+        }
+
         // Create account configs
-        let accounts = private_keys
+        let accounts = relayer_keys
             .into_iter()
-            .map(|private_key| AccountConfig {
-                private_key,
+            .map(|key| AccountConfig {
+                key,
                 min_gas_wei: min_gas_wei.clone(),
             })
             .collect();
@@ -76,9 +220,11 @@ impl RelayerConfig {
         let scheduler = match scheduler_str.to_lowercase().as_str() {
             "round_robin" => SchedulerType::RoundRobin,
             "random" => SchedulerType::Random,
+            "least_pending" => SchedulerType::LeastPending,
+            "balance_weighted" => SchedulerType::BalanceWeighted,
             _ => {
                 return Err(format!(
-                    "Invalid RELAYER_SCHEDULER value: {scheduler_str}. Must be one of: round_robin, random"
+                    "Invalid RELAYER_SCHEDULER value: {scheduler_str}. Must be one of: round_robin, random, least_pending, balance_weighted"
                 )
                 .into());
             }
@@ -93,18 +239,240 @@ impl RelayerConfig {
         // Parse BEBE address
         let bebe_address = env::var("BEBE_ADDRESS").ok();
 
+        // Parse fulfillment mode, batching through BEBE whenever it is configured
+        let default_mode = if bebe_address.is_some() {
+            "batch"
+        } else {
+            "single"
+        };
+        let fulfillment_mode_str =
+            env::var("FULFILLMENT_MODE").unwrap_or_else(|_| default_mode.to_string());
+
+        let fulfillment_mode = match fulfillment_mode_str.to_lowercase().as_str() {
+            "batch" => {
+                if bebe_address.is_none() {
+                    return Err("FULFILLMENT_MODE=batch requires BEBE_ADDRESS".into());
+                }
+                FulfillmentMode::Batch
+            }
+            "single" => FulfillmentMode::Single,
+            "multicall3" => {
+                let multicall3_address = match env::var("MULTICALL3_ADDRESS") {
+                    Ok(address) => address
+                        .parse::<Address>()
+                        .map_err(|_| "Invalid MULTICALL3_ADDRESS format")?,
+                    Err(_) => MULTICALL3_ADDRESS,
+                };
+                FulfillmentMode::Multicall3(multicall3_address)
+            }
+            _ => {
+                return Err(format!(
+                    "Invalid FULFILLMENT_MODE value: {fulfillment_mode_str}. Must be one of: batch, single, multicall3"
+                )
+                .into());
+            }
+        };
+
         // Parse batch size
         let batch_size = env::var("BATCH_SIZE")
             .unwrap_or_else(|_| "100".to_string())
             .parse::<usize>()
             .map_err(|_| "Invalid BATCH_SIZE value")?;
 
+        // Parse batch gas budget
+        let batch_gas_budget = env::var("BATCH_GAS_BUDGET")
+            .unwrap_or_else(|_| "15000000".to_string())
+            .parse::<u64>()
+            .map_err(|_| "Invalid BATCH_GAS_BUDGET value")?;
+
+        // Parse failure isolation mode
+        let isolate_failures = env::var("BATCH_ISOLATE_FAILURES")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse::<bool>()
+            .map_err(|_| "Invalid BATCH_ISOLATE_FAILURES value")?;
+
+        // Parse confirmation depth
+        let confirmation_depth = env::var("CONFIRMATION_DEPTH")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u64>()
+            .map_err(|_| "Invalid CONFIRMATION_DEPTH value")?;
+
+        // Parse stuck transaction handling
+        let stuck_tx_blocks = env::var("RELAYER_STUCK_TX_BLOCKS")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse::<u64>()
+            .map_err(|_| "Invalid RELAYER_STUCK_TX_BLOCKS value")?;
+
+        let fee_bump_percent = env::var("RELAYER_FEE_BUMP_PERCENT")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse::<u64>()
+            .map_err(|_| "Invalid RELAYER_FEE_BUMP_PERCENT value")?;
+
+        let tx_timeout_blocks = env::var("RELAYER_TX_TIMEOUT_BLOCKS")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse::<u64>()
+            .map_err(|_| "Invalid RELAYER_TX_TIMEOUT_BLOCKS value")?;
+
+        let max_fee_per_gas_wei = env::var("RELAYER_MAX_FEE_PER_GAS_WEI")
+            .unwrap_or_else(|_| "500000000000".to_string()) // 500 gwei default
+            .parse::<u128>()
+            .map_err(|_| "Invalid RELAYER_MAX_FEE_PER_GAS_WEI value")?;
+
+        // Parse gas strategy
+        let gas_strategy_str =
+            env::var("RELAYER_GAS_STRATEGY").unwrap_or_else(|_| "provider".to_string());
+
+        let gas_strategy = match gas_strategy_str.to_lowercase().as_str() {
+            "provider" => GasStrategyType::Provider,
+            "fixed" => GasStrategyType::Fixed,
+            "fee_history" => GasStrategyType::FeeHistory,
+            "urgency" => GasStrategyType::Urgency,
+            _ => {
+                return Err(format!(
+                    "Invalid RELAYER_GAS_STRATEGY value: {gas_strategy_str}. Must be one of: provider, fixed, fee_history, urgency"
+                )
+                .into());
+            }
+        };
+
+        // Parse treasury top-ups, disabled without a treasury key
+        let mut treasury_keys = Vec::new();
+        let mut treasury_configured = false;
+        if let Some(private_key) = keys::secret_env("TREASURY_PRIVATE_KEY")? {
+            treasury_keys.extend(keys::parse_private_keys(&private_key));
+            treasury_configured = true;
+        }
+        if let Ok(keystore_path) = env::var("TREASURY_KEYSTORE") {
+            let password_file = env::var("TREASURY_KEYSTORE_PASSWORD_FILE")
+                .ok()
+                .map(PathBuf::from);
+            treasury_keys.extend(keys::keystores(&keystore_path, password_file.as_deref())?);
+            treasury_configured = true;
+        }
+
+        if treasury_configured && treasury_keys.len() != 1 {
+            return Err(format!(
+                "Exactly one treasury key must be given through TREASURY_PRIVATE_KEY or TREASURY_KEYSTORE, found {}",
+                treasury_keys.len()
+            )
+            .into());
+        }
+        let treasury_key = treasury_keys.pop();
+
+        let treasury = match treasury_key {
+            Some(key) => {
+                let low_water_wei = env::var("TREASURY_LOW_WATER_WEI")
+                    .unwrap_or_else(|_| "10000000000000000".to_string()) // 0.01 ETH default
+                    .parse::<u128>()
+                    .map_err(|_| "Invalid TREASURY_LOW_WATER_WEI value")?;
+
+                let high_water_wei = env::var("TREASURY_HIGH_WATER_WEI")
+                    .unwrap_or_else(|_| "50000000000000000".to_string()) // 0.05 ETH default
+                    .parse::<u128>()
+                    .map_err(|_| "Invalid TREASURY_HIGH_WATER_WEI value")?;
+
+                if high_water_wei <= low_water_wei {
+                    return Err(
+                        "TREASURY_HIGH_WATER_WEI must be above TREASURY_LOW_WATER_WEI".into(),
+                    );
+                }
+
+                let daily_cap_wei = env::var("TREASURY_DAILY_CAP_WEI")
+                    .unwrap_or_else(|_| "1000000000000000000".to_string()) // 1 ETH default
+                    .parse::<u128>()
+                    .map_err(|_| "Invalid TREASURY_DAILY_CAP_WEI value")?;
+
+                let check_interval_secs = env::var("TREASURY_CHECK_INTERVAL_SECS")
+                    .unwrap_or_else(|_| "60".to_string())
+                    .parse::<u64>()
+                    .map_err(|_| "Invalid TREASURY_CHECK_INTERVAL_SECS value")?;
+
+                Some(TreasuryConfig {
+                    key,
+                    low_water_wei,
+                    high_water_wei,
+                    daily_cap_wei,
+                    check_interval_secs,
+                })
+            }
+            None => None,
+        };
+
+        // Parse RPC endpoints
+        let rpc_urls: Vec<String> = env::var("RPC_URLS")
+            .or_else(|_| env::var("RPC_URL"))
+            .unwrap_or_else(|_| "http://127.0.0.1:8545".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(String::from)
+            .collect();
+
+        if rpc_urls.is_empty() {
+            return Err("No RPC endpoints found in RPC_URLS or RPC_URL".into());
+        }
+
+        let active_transports = env::var("RPC_ACTIVE_TRANSPORTS")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse::<usize>()
+            .map_err(|_| "Invalid RPC_ACTIVE_TRANSPORTS value")?
+            .clamp(1, rpc_urls.len());
+
+        let quorum = env::var("RPC_QUORUM")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<usize>()
+            .map_err(|_| "Invalid RPC_QUORUM value")?;
+
+        if quorum == 0 || quorum > rpc_urls.len() {
+            return Err(format!(
+                "RPC_QUORUM must be between 1 and the number of RPC endpoints ({})",
+                rpc_urls.len()
+            )
+            .into());
+        }
+
+        let rpc = RpcConfig {
+            urls: rpc_urls,
+            active_transports,
+            quorum,
+        };
+
+        // Parse randomness source
+        let randomness_source_str =
+            env::var("RANDOMNESS_SOURCE").unwrap_or_else(|_| "vrf".to_string());
+
+        let randomness_source = match randomness_source_str.to_lowercase().as_str() {
+            "vrf" => RandomnessSourceType::Vrf,
+            "os" => RandomnessSourceType::Os,
+            "chacha" => RandomnessSourceType::ChaCha,
+            "hmac_drbg" => RandomnessSourceType::HmacDrbg,
+            _ => {
+                return Err(format!(
+                    "Invalid RANDOMNESS_SOURCE value: {randomness_source_str}. Must be one of: vrf, os, chacha, hmac_drbg"
+                )
+                .into());
+            }
+        };
+
         Ok(Self {
             accounts,
+            rpc,
+            remote_signer,
             scheduler,
             pending_block_threshold,
             bebe_address,
+            fulfillment_mode,
             batch_size,
+            batch_gas_budget,
+            isolate_failures,
+            confirmation_depth,
+            stuck_tx_blocks,
+            fee_bump_percent,
+            tx_timeout_blocks,
+            max_fee_per_gas_wei,
+            gas_strategy,
+            treasury,
+            randomness_source,
         })
     }
 }
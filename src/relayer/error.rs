@@ -0,0 +1,49 @@
+use crate::oracle::CallFailure;
+use alloy::primitives::TxHash;
+
+/// Why a batch could not be fulfilled
+#[derive(Debug, Clone)]
+pub enum RelayerError {
+    /// The batch reverts in simulation and was not broadcast
+    Reverted(CallFailure),
+    /// Gas estimation failed for a reason other than a revert
+    GasEstimation(String),
+    /// The node rejected the transaction
+    Send(String),
+    /// The transaction was mined but reverted
+    MinedReverted(TxHash),
+    /// The nonce was freed by a self-transfer after the fee cap was reached
+    Cancelled(TxHash),
+    /// Nothing we sent was mined, within the block budget or before the nonce was used
+    Dropped(TxHash),
+    /// The transaction was sent but its receipt could not be fetched
+    Receipt { tx_hash: TxHash, error: String },
+    /// An RPC call failed
+    Rpc(String),
+}
+
+impl RelayerError {
+    /// Whether the failure comes from the calls themselves rather than the node or the account
+    pub const fn is_revert(&self) -> bool {
+        matches!(self, Self::Reverted(_) | Self::MinedReverted(_))
+    }
+}
+
+impl std::fmt::Display for RelayerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Reverted(failure) => write!(f, "batch reverts in simulation: {failure}"),
+            Self::GasEstimation(e) => write!(f, "gas estimation failed: {e}"),
+            Self::Send(e) => write!(f, "failed to send transaction: {e}"),
+            Self::MinedReverted(tx_hash) => write!(f, "transaction {tx_hash} reverted"),
+            Self::Cancelled(tx_hash) => write!(f, "transaction cancelled by {tx_hash}"),
+            Self::Dropped(tx_hash) => write!(f, "transaction {tx_hash} was dropped"),
+            Self::Receipt { tx_hash, error } => {
+                write!(f, "failed to get receipt for {tx_hash}: {error}")
+            }
+            Self::Rpc(e) => write!(f, "RPC error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RelayerError {}
@@ -0,0 +1,184 @@
+//! Treasury top-ups for relayer accounts.
+//!
+//! Every `check_interval_secs` the funder refreshes each relayer's cached balance. An
+//! account below the low-water mark is sent enough ETH from the treasury to reach the
+//! high-water mark, as long as the treasury's spend over the last 24 hours, read back
+//! from `relayer_topups`, stays within the daily cap. A top-up is reserved there before
+//! it is sent, so it counts against the cap even if its receipt never arrives.
+
+use super::account::{format_ether, RelayerAccount};
+use super::config::TreasuryConfig;
+use super::gas::{self, GasStrategy};
+use super::metrics;
+use super::rpc::RpcPool;
+use crate::database::{QueueDatabase, TopupStatus};
+use alloy::{
+    network::{Ethereum, EthereumWallet},
+    primitives::{Address, U256},
+    providers::{Provider, ProviderBuilder},
+    rpc::types::TransactionRequest,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// How long a top-up may wait for its receipt before the funder moves on
+const TOPUP_RECEIPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub struct Funder {
+    pub address: Address,
+    provider: Arc<dyn Provider<Ethereum> + Send + Sync>,
+    low_water: U256,
+    high_water: U256,
+    daily_cap: U256,
+    check_interval: Duration,
+    gas_strategy: Arc<dyn GasStrategy>,
+    max_fee_per_gas: u128,
+}
+
+impl Funder {
+    pub fn new(
+        config: &TreasuryConfig,
+        rpc: &RpcPool,
+        gas_strategy: Arc<dyn GasStrategy>,
+        max_fee_per_gas: u128,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let signer = config.key.signer()?;
+        let address = signer.address();
+
+        let provider: Arc<dyn Provider<Ethereum> + Send + Sync> = Arc::new(
+            ProviderBuilder::new()
+                .wallet(EthereumWallet::from(signer))
+                .connect_client(rpc.client()),
+        );
+
+        Ok(Self {
+            address,
+            provider,
+            low_water: U256::from(config.low_water_wei),
+            high_water: U256::from(config.high_water_wei),
+            daily_cap: U256::from(config.daily_cap_wei),
+            check_interval: Duration::from_secs(config.check_interval_secs),
+            gas_strategy,
+            max_fee_per_gas,
+        })
+    }
+
+    /// Check the relayer balances on a timer and top up the low ones, while the treasury
+    /// key is in `owned_keys`
+    pub fn spawn(
+        self: Arc<Self>,
+        accounts: Vec<Arc<RelayerAccount>>,
+        owned_keys: Arc<Mutex<HashSet<Address>>>,
+        queue_db: QueueDatabase,
+    ) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.check_interval);
+            loop {
+                interval.tick().await;
+                // Another replica tops up with this treasury
+                if !owned_keys.lock().await.contains(&self.address) {
+                    continue;
+                }
+                for account in accounts.iter() {
+                    if let Err(e) = self.top_up(account, &queue_db).await {
+                        warn!("Failed to top up {}: {}", account.address, e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Top up `account` if it is below the low-water mark
+    async fn top_up(
+        &self,
+        account: &RelayerAccount,
+        queue_db: &QueueDatabase,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        account.update_balance().await?;
+        let balance = account.cached_balance().await;
+        if balance >= self.low_water {
+            return Ok(());
+        }
+
+        let spent = queue_db.get_treasury_spent_last_day(self.address).await?;
+        let remaining = self.daily_cap.saturating_sub(spent);
+        let amount = (self.high_water - balance).min(remaining);
+        if amount.is_zero() {
+            warn!(
+                "Account {} is low ({} ETH) but the treasury daily cap of {} ETH is spent",
+                account.address,
+                format_ether(balance),
+                format_ether(self.daily_cap)
+            );
+            metrics::record_topup_capped(&account.address.to_string());
+            return Ok(());
+        }
+
+        info!(
+            "Topping up {} with {} ETH from treasury {}",
+            account.address,
+            format_ether(amount),
+            self.address
+        );
+
+        let fees = gas::capped_estimate(
+            self.gas_strategy.as_ref(),
+            self.provider.as_ref(),
+            Duration::ZERO,
+            self.max_fee_per_gas,
+        )
+        .await?;
+        let tx = TransactionRequest::default()
+            .from(self.address)
+            .to(account.address)
+            .value(amount)
+            .max_fee_per_gas(fees.max_fee_per_gas)
+            .max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+
+        let topup = queue_db
+            .reserve_topup(self.address, account.address, amount)
+            .await?;
+        let pending = match self.provider.send_transaction(tx).await {
+            Ok(pending) => pending,
+            Err(e) => {
+                queue_db
+                    .update_topup(topup, TopupStatus::Failed, None)
+                    .await?;
+                return Err(e.into());
+            }
+        };
+        let tx_hash = *pending.tx_hash();
+        queue_db
+            .update_topup(topup, TopupStatus::Sent, Some(tx_hash))
+            .await?;
+
+        // Left as sent on timeout: it may still be mined, so it keeps counting against the cap
+        let receipt = pending
+            .with_timeout(Some(TOPUP_RECEIPT_TIMEOUT))
+            .get_receipt()
+            .await?;
+        if !receipt.status() {
+            queue_db
+                .update_topup(topup, TopupStatus::Failed, None)
+                .await?;
+            return Err(format!("Top-up transaction {tx_hash} reverted").into());
+        }
+
+        queue_db
+            .update_topup(topup, TopupStatus::Confirmed, None)
+            .await?;
+        metrics::record_topup(&account.address.to_string(), amount);
+        metrics::record_treasury_spend(spent + amount);
+
+        account.update_balance().await?;
+        info!(
+            "Topped up {} in transaction {}",
+            account.address, receipt.transaction_hash
+        );
+
+        Ok(())
+    }
+}
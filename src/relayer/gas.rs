@@ -0,0 +1,280 @@
+//! EIP-1559 fee pricing for relayer transactions.
+//!
+//! Every transaction the relayer and the treasury send is priced through
+//! `capped_estimate`, which clamps the strategy's output to `RELAYER_MAX_FEE_PER_GAS_WEI`,
+//! so a fee spike can delay fulfillments but never drain relayer balances.
+
+use super::config::GasStrategyType;
+use alloy::transports::TransportError;
+use alloy::{eips::BlockNumberOrTag, network::Ethereum, providers::Provider};
+use async_trait::async_trait;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Fees for one transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimate {
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl FeeEstimate {
+    /// Clamp to the fee ceiling, keeping the priority fee within the max fee
+    pub fn capped(self, max_fee_per_gas: u128) -> Self {
+        let max_fee_per_gas = self.max_fee_per_gas.min(max_fee_per_gas);
+        Self {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas.min(max_fee_per_gas),
+        }
+    }
+
+    fn scaled(self, percent: u64) -> Self {
+        let scale = |fee: u128| fee * (100 + percent as u128) / 100;
+        Self {
+            max_fee_per_gas: scale(self.max_fee_per_gas),
+            max_priority_fee_per_gas: scale(self.max_priority_fee_per_gas),
+        }
+    }
+}
+
+#[async_trait]
+pub trait GasStrategy: Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Fees for a transaction whose oldest request has been queued for `queue_age`
+    async fn estimate(
+        &self,
+        provider: &(dyn Provider<Ethereum> + Send + Sync),
+        queue_age: Duration,
+    ) -> Result<FeeEstimate, TransportError>;
+}
+
+/// Fees from `strategy`, clamped to the fee ceiling
+pub async fn capped_estimate(
+    strategy: &dyn GasStrategy,
+    provider: &(dyn Provider<Ethereum> + Send + Sync),
+    queue_age: Duration,
+    max_fee_per_gas: u128,
+) -> Result<FeeEstimate, TransportError> {
+    let fees = strategy.estimate(provider, queue_age).await?;
+
+    let capped = fees.capped(max_fee_per_gas);
+    if capped != fees {
+        warn!(
+            "{} gas strategy asked for {} wei max fee, capped at {} wei",
+            strategy.name(),
+            fees.max_fee_per_gas,
+            max_fee_per_gas
+        );
+    }
+
+    Ok(capped)
+}
+
+/// The provider's own EIP-1559 estimate, as used by alloy's gas filler
+pub struct ProviderGas;
+
+#[async_trait]
+impl GasStrategy for ProviderGas {
+    fn name(&self) -> &'static str {
+        "provider"
+    }
+
+    async fn estimate(
+        &self,
+        provider: &(dyn Provider<Ethereum> + Send + Sync),
+        _queue_age: Duration,
+    ) -> Result<FeeEstimate, TransportError> {
+        let estimate = provider.estimate_eip1559_fees().await?;
+        Ok(FeeEstimate {
+            max_fee_per_gas: estimate.max_fee_per_gas,
+            max_priority_fee_per_gas: estimate.max_priority_fee_per_gas,
+        })
+    }
+}
+
+/// Constant fees
+pub struct FixedGas {
+    fees: FeeEstimate,
+}
+
+impl FixedGas {
+    pub const fn new(fees: FeeEstimate) -> Self {
+        Self { fees }
+    }
+}
+
+#[async_trait]
+impl GasStrategy for FixedGas {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    async fn estimate(
+        &self,
+        _provider: &(dyn Provider<Ethereum> + Send + Sync),
+        _queue_age: Duration,
+    ) -> Result<FeeEstimate, TransportError> {
+        Ok(self.fees)
+    }
+}
+
+/// Priority fee at a percentile of recent blocks' rewards, on top of twice the next base fee
+pub struct FeeHistoryGas {
+    percentile: f64,
+    blocks: u64,
+}
+
+impl FeeHistoryGas {
+    pub const fn new(percentile: f64, blocks: u64) -> Self {
+        Self { percentile, blocks }
+    }
+}
+
+#[async_trait]
+impl GasStrategy for FeeHistoryGas {
+    fn name(&self) -> &'static str {
+        "fee_history"
+    }
+
+    async fn estimate(
+        &self,
+        provider: &(dyn Provider<Ethereum> + Send + Sync),
+        _queue_age: Duration,
+    ) -> Result<FeeEstimate, TransportError> {
+        let history = provider
+            .get_fee_history(self.blocks, BlockNumberOrTag::Latest, &[self.percentile])
+            .await?;
+
+        let base_fee = history.next_block_base_fee().unwrap_or_default();
+
+        // Median across blocks of the reward at the requested percentile
+        let mut rewards: Vec<u128> = history
+            .reward
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|block| block.first().copied())
+            .collect();
+        rewards.sort_unstable();
+        let priority_fee = rewards.get(rewards.len() / 2).copied().unwrap_or_default();
+
+        Ok(FeeEstimate {
+            max_fee_per_gas: base_fee * 2 + priority_fee,
+            max_priority_fee_per_gas: priority_fee,
+        })
+    }
+}
+
+/// Fee history pricing raised by `step_percent` for every `step` the oldest request has
+/// waited, up to `max_percent`
+pub struct UrgencyScaledGas {
+    base: FeeHistoryGas,
+    step: Duration,
+    step_percent: u64,
+    max_percent: u64,
+}
+
+impl UrgencyScaledGas {
+    pub const fn new(
+        base: FeeHistoryGas,
+        step: Duration,
+        step_percent: u64,
+        max_percent: u64,
+    ) -> Self {
+        Self {
+            base,
+            step,
+            step_percent,
+            max_percent,
+        }
+    }
+}
+
+#[async_trait]
+impl GasStrategy for UrgencyScaledGas {
+    fn name(&self) -> &'static str {
+        "urgency"
+    }
+
+    async fn estimate(
+        &self,
+        provider: &(dyn Provider<Ethereum> + Send + Sync),
+        queue_age: Duration,
+    ) -> Result<FeeEstimate, TransportError> {
+        let fees = self.base.estimate(provider, queue_age).await?;
+        let steps = queue_age.as_secs() / self.step.as_secs().max(1);
+        let percent = steps
+            .saturating_mul(self.step_percent)
+            .min(self.max_percent);
+
+        Ok(fees.scaled(percent))
+    }
+}
+
+/// Build the configured gas strategy, reading its parameters from the environment:
+/// `RELAYER_FIXED_MAX_FEE_WEI` and `RELAYER_FIXED_PRIORITY_FEE_WEI` (fixed),
+/// `RELAYER_FEE_HISTORY_PERCENTILE` and `RELAYER_FEE_HISTORY_BLOCKS` (fee_history, urgency),
+/// `RELAYER_URGENCY_STEP_SECS`, `RELAYER_URGENCY_STEP_PERCENT` and `RELAYER_URGENCY_MAX_PERCENT` (urgency)
+pub fn from_config(
+    strategy_type: &GasStrategyType,
+) -> Result<Arc<dyn GasStrategy>, Box<dyn std::error::Error + Send + Sync>> {
+    let strategy: Arc<dyn GasStrategy> = match strategy_type {
+        GasStrategyType::Provider => Arc::new(ProviderGas),
+        GasStrategyType::Fixed => {
+            let max_fee_per_gas = env::var("RELAYER_FIXED_MAX_FEE_WEI")
+                .map_err(|_| "RELAYER_FIXED_MAX_FEE_WEI environment variable is not set")?
+                .parse::<u128>()
+                .map_err(|_| "Invalid RELAYER_FIXED_MAX_FEE_WEI value")?;
+            let max_priority_fee_per_gas = env::var("RELAYER_FIXED_PRIORITY_FEE_WEI")
+                .unwrap_or_else(|_| "1000000000".to_string()) // 1 gwei default
+                .parse::<u128>()
+                .map_err(|_| "Invalid RELAYER_FIXED_PRIORITY_FEE_WEI value")?;
+            Arc::new(FixedGas::new(FeeEstimate {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            }))
+        }
+        GasStrategyType::FeeHistory => Arc::new(fee_history_from_env()?),
+        GasStrategyType::Urgency => {
+            let step_secs = env::var("RELAYER_URGENCY_STEP_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse::<u64>()
+                .map_err(|_| "Invalid RELAYER_URGENCY_STEP_SECS value")?;
+            let step_percent = env::var("RELAYER_URGENCY_STEP_PERCENT")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse::<u64>()
+                .map_err(|_| "Invalid RELAYER_URGENCY_STEP_PERCENT value")?;
+            let max_percent = env::var("RELAYER_URGENCY_MAX_PERCENT")
+                .unwrap_or_else(|_| "200".to_string())
+                .parse::<u64>()
+                .map_err(|_| "Invalid RELAYER_URGENCY_MAX_PERCENT value")?;
+            Arc::new(UrgencyScaledGas::new(
+                fee_history_from_env()?,
+                Duration::from_secs(step_secs),
+                step_percent,
+                max_percent,
+            ))
+        }
+    };
+
+    info!("Using {} gas strategy", strategy.name());
+    Ok(strategy)
+}
+
+fn fee_history_from_env() -> Result<FeeHistoryGas, Box<dyn std::error::Error + Send + Sync>> {
+    let percentile = env::var("RELAYER_FEE_HISTORY_PERCENTILE")
+        .unwrap_or_else(|_| "50".to_string())
+        .parse::<f64>()
+        .ok()
+        .filter(|p| (0.0..=100.0).contains(p))
+        .ok_or("Invalid RELAYER_FEE_HISTORY_PERCENTILE value")?;
+    let blocks = env::var("RELAYER_FEE_HISTORY_BLOCKS")
+        .unwrap_or_else(|_| "10".to_string())
+        .parse::<u64>()
+        .map_err(|_| "Invalid RELAYER_FEE_HISTORY_BLOCKS value")?;
+
+    Ok(FeeHistoryGas::new(percentile, blocks))
+}
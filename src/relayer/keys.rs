@@ -0,0 +1,119 @@
+//! Loading of relayer and treasury signing keys.
+//!
+//! Keys come from raw hex private keys or Ethereum JSON keystores (scrypt or pbkdf2).
+//! Secret values can be read from `<NAME>_FILE` instead of `<NAME>`, for container
+//! secrets, and are wiped from memory once the signer is built. Key material is never
+//! printed: `Debug` only shows where a key comes from.
+
+use alloy::signers::local::PrivateKeySigner;
+use std::env;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Where a signing key is loaded from
+#[derive(Clone)]
+pub enum KeySource {
+    PrivateKey(Zeroizing<String>),
+    Keystore {
+        path: PathBuf,
+        password: Zeroizing<String>,
+    },
+}
+
+impl KeySource {
+    /// Build the signer, decrypting the keystore if needed
+    pub fn signer(&self) -> Result<PrivateKeySigner, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Self::PrivateKey(private_key) => private_key
+                .parse()
+                .map_err(|e| format!("Failed to parse private key: {e}").into()),
+            Self::Keystore { path, password } => {
+                PrivateKeySigner::decrypt_keystore(path, password.as_bytes()).map_err(|e| {
+                    format!("Failed to decrypt keystore {}: {e}", path.display()).into()
+                })
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PrivateKey(_) => write!(f, "PrivateKey(<redacted>)"),
+            Self::Keystore { path, .. } => write!(f, "Keystore({})", path.display()),
+        }
+    }
+}
+
+/// Read a secret from `name`, or from the file named by `<name>_FILE`
+pub fn secret_env(
+    name: &str,
+) -> Result<Option<Zeroizing<String>>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Ok(value) = env::var(name) {
+        return Ok(Some(Zeroizing::new(value)));
+    }
+
+    let file_var = format!("{name}_FILE");
+    match env::var(&file_var) {
+        Ok(path) => {
+            let value = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {file_var} ({path}): {e}"))?;
+            Ok(Some(Zeroizing::new(value)))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Split a list of private keys separated by commas or newlines
+pub fn parse_private_keys(value: &str) -> Vec<KeySource> {
+    value
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| KeySource::PrivateKey(Zeroizing::new(key.to_string())))
+        .collect()
+}
+
+/// Keystore sources for a list of paths separated by commas, sharing one password.
+///
+/// The password is read from `password_file`, or prompted for on the terminal once.
+pub fn keystores(
+    paths: &str,
+    password_file: Option<&Path>,
+) -> Result<Vec<KeySource>, Box<dyn std::error::Error + Send + Sync>> {
+    let paths: Vec<PathBuf> = paths
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect();
+
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let password = match password_file {
+        Some(password_file) => {
+            let password = Zeroizing::new(std::fs::read_to_string(password_file).map_err(|e| {
+                format!(
+                    "Failed to read keystore password file {}: {e}",
+                    password_file.display()
+                )
+            })?);
+            // Files usually end with a newline that is not part of the password
+            Zeroizing::new(password.trim_end_matches(['\r', '\n']).to_string())
+        }
+        None => Zeroizing::new(rpassword::prompt_password(format!(
+            "Password for {} keystore(s): ",
+            paths.len()
+        ))?),
+    };
+
+    Ok(paths
+        .into_iter()
+        .map(|path| KeySource::Keystore {
+            path,
+            password: password.clone(),
+        })
+        .collect())
+}
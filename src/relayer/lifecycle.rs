@@ -0,0 +1,239 @@
+//! Tracking of sent transactions until their nonce is mined.
+//!
+//! A transaction still pending `stuck_after_blocks` blocks after its last broadcast is
+//! replaced with the same nonce and EIP-1559 fees bumped by `fee_bump_percent`. When the
+//! bump after next would exceed `max_fee_per_gas`, the nonce is cancelled instead with a
+//! zero-value self-transfer priced at the cap, which is still a valid replacement. The
+//! wait ends after `timeout_blocks` blocks, or as soon as the nonce is used by a
+//! transaction that is not one of ours.
+
+use super::{metrics, RelayerError};
+use alloy::{
+    consensus::Transaction as _,
+    network::Ethereum,
+    primitives::{Address, TxHash, U256},
+    providers::Provider,
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    transports::TransportError,
+};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Minimum fee increase nodes accept for a replacement transaction
+const MIN_FEE_BUMP_PERCENT: u64 = 10;
+
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const CANCEL_GAS_LIMIT: u64 = 21_000;
+
+#[derive(Debug, Clone)]
+pub struct TxLifecycleConfig {
+    pub stuck_after_blocks: u64,
+    pub fee_bump_percent: u64,
+    pub max_fee_per_gas: u128,
+    /// Blocks after the first broadcast before the transaction is given up as dropped
+    pub timeout_blocks: u64,
+}
+
+/// Fees and hashes of every transaction broadcast for one nonce
+struct TrackedTx {
+    nonce: Option<u64>,
+    max_fee_per_gas: u128,
+    max_priority_fee_per_gas: u128,
+    hashes: Vec<TxHash>,
+    /// First block at which the next replacement may be broadcast
+    next_broadcast_block: u64,
+    deadline_block: u64,
+    cancellation: Option<TxHash>,
+}
+
+/// Where a tracked nonce stands after a poll
+enum TxStatus {
+    Pending,
+    Mined(TransactionReceipt),
+    Cancelled(TransactionReceipt),
+    Dropped,
+}
+
+pub struct TxLifecycle {
+    config: TxLifecycleConfig,
+}
+
+impl TxLifecycle {
+    pub const fn new(config: TxLifecycleConfig) -> Self {
+        Self { config }
+    }
+
+    /// Wait until `tx_hash` or one of its replacements is mined.
+    ///
+    /// `tx` is the request that was sent, rebroadcast with bumped fees while stuck. Fails
+    /// with `Cancelled` when the cancellation is mined instead, and with `Dropped` when the
+    /// nonce is used by another transaction or nothing was mined within the block budget.
+    pub async fn wait_for_receipt(
+        &self,
+        provider: &(dyn Provider<Ethereum> + Send + Sync),
+        from: Address,
+        tx: &TransactionRequest,
+        tx_hash: TxHash,
+    ) -> Result<TransactionReceipt, RelayerError> {
+        let block = provider
+            .get_block_number()
+            .await
+            .map_err(|e| RelayerError::Receipt {
+                tx_hash,
+                error: e.to_string(),
+            })?;
+
+        let mut tracked = TrackedTx {
+            nonce: tx.nonce,
+            max_fee_per_gas: 0,
+            max_priority_fee_per_gas: 0,
+            hashes: vec![tx_hash],
+            next_broadcast_block: block + self.config.stuck_after_blocks,
+            deadline_block: block + self.config.timeout_blocks,
+            cancellation: None,
+        };
+
+        loop {
+            tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+
+            // RPC errors are transient here, the transaction may still be mined
+            match self.poll(provider, from, tx, &mut tracked).await {
+                Ok(TxStatus::Pending) => {}
+                Ok(TxStatus::Mined(receipt)) => return Ok(receipt),
+                Ok(TxStatus::Cancelled(receipt)) => {
+                    return Err(RelayerError::Cancelled(receipt.transaction_hash))
+                }
+                Ok(TxStatus::Dropped) => return Err(RelayerError::Dropped(tx_hash)),
+                Err(e) => warn!("Failed to check transaction {}: {}", tx_hash, e),
+            }
+        }
+    }
+
+    async fn poll(
+        &self,
+        provider: &(dyn Provider<Ethereum> + Send + Sync),
+        from: Address,
+        tx: &TransactionRequest,
+        tracked: &mut TrackedTx,
+    ) -> Result<TxStatus, TransportError> {
+        // Read before the receipts, so a nonce used past this point is one of ours
+        let block = provider.get_block_number().await?;
+        let mined_nonce = provider.get_transaction_count(from).latest().await?;
+
+        for hash in tracked.hashes.iter() {
+            if let Some(receipt) = provider.get_transaction_receipt(*hash).await? {
+                if tracked.cancellation == Some(*hash) {
+                    return Ok(TxStatus::Cancelled(receipt));
+                }
+                return Ok(TxStatus::Mined(receipt));
+            }
+        }
+
+        if tracked.nonce.is_some_and(|nonce| mined_nonce > nonce) {
+            warn!(
+                "Nonce {} of {} was used by another transaction",
+                tracked.nonce.unwrap_or_default(),
+                from
+            );
+            return Ok(TxStatus::Dropped);
+        }
+        if block >= tracked.deadline_block {
+            warn!(
+                "Transaction {} from {} not mined within {} blocks, giving up",
+                tracked.hashes[0], from, self.config.timeout_blocks
+            );
+            return Ok(TxStatus::Dropped);
+        }
+
+        // Learn the fees chosen when sending, and the nonce if the provider's fillers chose it
+        if tracked.max_fee_per_gas == 0 {
+            if let Some(sent) = provider.get_transaction_by_hash(tracked.hashes[0]).await? {
+                tracked.nonce = Some(sent.nonce());
+                tracked.max_fee_per_gas = sent.max_fee_per_gas();
+                tracked.max_priority_fee_per_gas = sent
+                    .max_priority_fee_per_gas()
+                    .unwrap_or(tracked.max_fee_per_gas);
+            }
+            return Ok(TxStatus::Pending);
+        }
+        let Some(nonce) = tracked.nonce else {
+            return Ok(TxStatus::Pending);
+        };
+
+        if tracked.cancellation.is_some() || block < tracked.next_broadcast_block {
+            return Ok(TxStatus::Pending);
+        }
+
+        let mut max_fee_per_gas = self.bump(tracked.max_fee_per_gas);
+        if max_fee_per_gas > self.config.max_fee_per_gas {
+            // No replacement can be both accepted and within the cap, wait for the deadline
+            return Ok(TxStatus::Pending);
+        }
+        // The last bump within the cap goes to the cancellation, at the cap itself
+        let cancel = self.bump(max_fee_per_gas) > self.config.max_fee_per_gas;
+        if cancel {
+            max_fee_per_gas = self.config.max_fee_per_gas;
+        }
+        let max_priority_fee_per_gas = self
+            .bump(tracked.max_priority_fee_per_gas)
+            .min(max_fee_per_gas);
+
+        let replacement = if cancel {
+            warn!(
+                "Fee cap of {} wei reached for nonce {} from {}, cancelling",
+                self.config.max_fee_per_gas, nonce, from
+            );
+            TransactionRequest::default()
+                .from(from)
+                .to(from)
+                .value(U256::ZERO)
+                .gas_limit(CANCEL_GAS_LIMIT)
+        } else {
+            warn!(
+                "Transaction with nonce {} from {} stuck, replacing with max fee {} wei",
+                nonce, from, max_fee_per_gas
+            );
+            tx.clone()
+        }
+        .nonce(nonce)
+        .max_fee_per_gas(max_fee_per_gas)
+        .max_priority_fee_per_gas(max_priority_fee_per_gas);
+
+        match provider.send_transaction(replacement).await {
+            Ok(pending) => {
+                let hash = *pending.tx_hash();
+                info!("Broadcast replacement {} for nonce {}", hash, nonce);
+                if cancel {
+                    metrics::record_cancellation(&from.to_string());
+                    tracked.cancellation = Some(hash);
+                } else {
+                    metrics::record_fee_bump(&from.to_string());
+                }
+                tracked.hashes.push(hash);
+                tracked.max_fee_per_gas = max_fee_per_gas;
+                tracked.max_priority_fee_per_gas = max_priority_fee_per_gas;
+                tracked.next_broadcast_block = block + self.config.stuck_after_blocks;
+            }
+            Err(e) => {
+                // "nonce too low" means a transaction for the nonce was mined, found on
+                // the next poll. Anything else, "underpriced" included, is retried on the
+                // next block, bumping from the rejected fees.
+                debug!("Failed to replace nonce {}: {}", nonce, e);
+                if e.to_string().contains("underpriced") {
+                    tracked.max_fee_per_gas = max_fee_per_gas;
+                    tracked.max_priority_fee_per_gas = max_priority_fee_per_gas;
+                }
+                tracked.next_broadcast_block = block + 1;
+            }
+        }
+
+        Ok(TxStatus::Pending)
+    }
+
+    /// Bump a fee enough for nodes to accept the replacement
+    fn bump(&self, fee: u128) -> u128 {
+        let percent = self.config.fee_bump_percent.max(MIN_FEE_BUMP_PERCENT) as u128;
+        (fee * (100 + percent)).div_ceil(100).max(fee + 1)
+    }
+}
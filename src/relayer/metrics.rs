@@ -1,4 +1,6 @@
-use metrics::{counter, describe_counter, describe_histogram, histogram};
+use super::account::AccountMetrics;
+use alloy::primitives::U256;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
 use std::sync::Once;
 
 static INIT: Once = Once::new();
@@ -27,6 +29,55 @@ pub fn init_metrics() {
             "Total number of batch fulfillment transactions"
         );
         describe_histogram!("batch_size", "Size of batches being processed");
+        describe_counter!(
+            "relayer_fee_bumps_total",
+            "Total number of stuck transactions replaced with bumped fees"
+        );
+        describe_counter!(
+            "relayer_cancellations_total",
+            "Total number of stuck transactions cancelled at the fee cap"
+        );
+        describe_gauge!(
+            "relayer_transactions_sent",
+            "Number of transactions sent by the relayer account"
+        );
+        describe_gauge!(
+            "relayer_transactions_failed",
+            "Number of failed transactions of the relayer account"
+        );
+        describe_gauge!(
+            "relayer_nonce_next",
+            "Nonce the relayer account will use for its next transaction"
+        );
+        describe_gauge!(
+            "relayer_nonce_chain_pending",
+            "Pending nonce of the relayer account reported by the chain"
+        );
+        describe_gauge!(
+            "relayer_nonce_resyncs",
+            "Number of times the cached nonce was resynchronised with the chain"
+        );
+        describe_gauge!(
+            "relayer_nonce_gaps_filled",
+            "Number of no-op transactions sent to fill nonce gaps"
+        );
+        describe_counter!(
+            "relayer_topups_total",
+            "Total number of treasury top-ups sent to the relayer account"
+        );
+        describe_histogram!("relayer_topup_eth", "ETH sent per treasury top-up");
+        describe_counter!(
+            "relayer_topups_capped_total",
+            "Total number of top-ups skipped because the treasury daily cap was spent"
+        );
+        describe_gauge!(
+            "treasury_spent_last_day_eth",
+            "ETH sent by the treasury over the last 24 hours"
+        );
+        describe_counter!(
+            "rpc_quorum_failures_total",
+            "Total number of critical reads on which the RPC endpoints did not reach quorum"
+        );
     });
 }
 
@@ -69,3 +120,66 @@ pub fn record_batch_unfulfilled(batch_size: usize) {
     counter!("relayer_batch_unfulfilled_total").increment(1);
     histogram!("batch_size").record(batch_size as f64);
 }
+
+/// Record a stuck transaction replaced with bumped fees
+pub fn record_fee_bump(address: &str) {
+    counter!(
+        "relayer_fee_bumps_total",
+        "address" => address.to_string()
+    )
+    .increment(1);
+}
+
+/// Record a stuck transaction cancelled at the fee cap
+pub fn record_cancellation(address: &str) {
+    counter!(
+        "relayer_cancellations_total",
+        "address" => address.to_string()
+    )
+    .increment(1);
+}
+
+/// Record the transaction counters and nonce tracking state of an account
+pub fn record_account_metrics(address: &str, account_metrics: &AccountMetrics) {
+    gauge!("relayer_transactions_sent", "address" => address.to_string())
+        .set(account_metrics.total_transactions as f64);
+    gauge!("relayer_transactions_failed", "address" => address.to_string())
+        .set(account_metrics.total_failures as f64);
+
+    let nonce = &account_metrics.nonce;
+    if let Some(next) = nonce.next() {
+        gauge!("relayer_nonce_next", "address" => address.to_string()).set(next as f64);
+    }
+    if let Some(chain_pending) = nonce.chain_pending {
+        gauge!("relayer_nonce_chain_pending", "address" => address.to_string())
+            .set(chain_pending as f64);
+    }
+    gauge!("relayer_nonce_resyncs", "address" => address.to_string()).set(nonce.resyncs as f64);
+    gauge!("relayer_nonce_gaps_filled", "address" => address.to_string())
+        .set(nonce.gaps_filled as f64);
+}
+
+/// Record a treasury top-up
+pub fn record_topup(address: &str, amount_wei: U256) {
+    counter!("relayer_topups_total", "address" => address.to_string()).increment(1);
+    histogram!("relayer_topup_eth").record(wei_to_eth(amount_wei));
+}
+
+/// Record a top-up skipped at the treasury daily cap
+pub fn record_topup_capped(address: &str) {
+    counter!("relayer_topups_capped_total", "address" => address.to_string()).increment(1);
+}
+
+/// Record the treasury spend over the last 24 hours
+pub fn record_treasury_spend(spent_wei: U256) {
+    gauge!("treasury_spent_last_day_eth").set(wei_to_eth(spent_wei));
+}
+
+/// Record a critical read on which the RPC endpoints did not reach quorum
+pub fn record_quorum_failure(read: &str) {
+    counter!("rpc_quorum_failures_total", "read" => read.to_string()).increment(1);
+}
+
+fn wei_to_eth(wei: U256) -> f64 {
+    f64::from(wei) / 1e18
+}
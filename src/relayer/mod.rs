@@ -1,25 +1,85 @@
 mod account;
+mod batch_sizer;
 mod config;
+mod error;
+mod funder;
+pub mod gas;
+mod keys;
+mod lifecycle;
 pub mod metrics;
+mod nonce;
+mod rpc;
 mod scheduler;
+mod selection;
+mod signer;
 
-pub use account::RelayerAccount;
-pub use config::RelayerConfig;
+pub use account::{BroadcastStatus, RelayerAccount, SentBatch};
+pub use batch_sizer::BatchSizer;
+pub use config::{FulfillmentMode, RandomnessSourceType, RelayerConfig};
+pub use error::RelayerError;
+pub use lifecycle::{TxLifecycle, TxLifecycleConfig};
 pub use scheduler::Relayer;
 
+use alloy::primitives::U256;
+use std::time::Duration;
+
+/// Result of checking whether an account can take a batch
+#[derive(Debug, Clone)]
+pub enum Availability {
+    Available,
+    Skipped(SkipReason),
+}
+
+/// Why an account was skipped, with the values that led to it
 #[derive(Debug, Clone)]
 pub enum SkipReason {
-    InsufficientGas,
-    PendingTransaction,
-    RecentFailure,
+    InsufficientGas { balance: U256, min_balance: U256 },
+    PendingTransaction { pending: usize, threshold: u64 },
+    RecentFailure { cooldown_remaining: Duration },
+    InUse,
+    NotOwned,
+    BebeMissing,
+    RpcError(String),
+}
+
+impl SkipReason {
+    /// Label of the `relayer_skipped_total` metric
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::InsufficientGas { .. } => "insufficient_gas",
+            Self::PendingTransaction { .. } => "pending_transaction",
+            Self::RecentFailure { .. } => "recent_failure",
+            Self::InUse => "in_use",
+            Self::NotOwned => "not_owned",
+            Self::BebeMissing => "bebe_missing",
+            Self::RpcError(_) => "rpc_error",
+        }
+    }
 }
 
 impl std::fmt::Display for SkipReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::InsufficientGas => write!(f, "insufficient_gas"),
-            Self::PendingTransaction => write!(f, "pending_transaction"),
-            Self::RecentFailure => write!(f, "recent_failure"),
+            Self::InsufficientGas {
+                balance,
+                min_balance,
+            } => write!(
+                f,
+                "insufficient_gas (balance {balance} < minimum {min_balance} wei)"
+            ),
+            Self::PendingTransaction { pending, threshold } => write!(
+                f,
+                "pending_transaction ({pending} pending, threshold {threshold})"
+            ),
+            Self::RecentFailure { cooldown_remaining } => write!(
+                f,
+                "recent_failure ({}s of cooldown left)",
+                cooldown_remaining.as_secs()
+            ),
+            Self::InUse => write!(f, "in_use"),
+            Self::NotOwned => write!(f, "not_owned"),
+            Self::BebeMissing => write!(f, "bebe_missing"),
+            Self::RpcError(e) => write!(f, "rpc_error ({e})"),
         }
     }
 }
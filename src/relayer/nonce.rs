@@ -0,0 +1,147 @@
+//! Nonce management that can be checked and repaired against the chain.
+//!
+//! Works like alloy's cached nonce manager, but the cache is shared with the
+//! [`RelayerAccount`](super::RelayerAccount) so it can be compared with
+//! `eth_getTransactionCount(pending)` after failures and on a timer.
+
+use alloy::{
+    network::{Ethereum, Network},
+    primitives::Address,
+    providers::fillers::NonceManager,
+};
+use alloy::{providers::Provider, transports::TransportResult};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Snapshot of an account's nonce tracking, exposed through `get_metrics`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NonceState {
+    /// Last nonce handed out, `None` until first use or after a resync to nonce 0
+    pub last_used: Option<u64>,
+    /// Pending nonce reported by the chain at the last check
+    pub chain_pending: Option<u64>,
+    pub resyncs: u64,
+    pub gaps_filled: u64,
+    /// Gap seen at the last check, filled if it is still there at the next one
+    suspected_gap: Option<(u64, u64)>,
+}
+
+impl NonceState {
+    /// Nonce the next transaction will use, if known
+    pub fn next(&self) -> Option<u64> {
+        self.last_used.map(|nonce| nonce + 1)
+    }
+}
+
+/// How the cached nonce compares with the chain's pending nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceDrift {
+    InSync,
+    /// Nonces were used outside this process, sends would fail with "nonce too low"
+    Behind {
+        next: u64,
+        chain_pending: u64,
+    },
+    /// Nonces were handed out but never reached the chain
+    Ahead {
+        next: u64,
+        chain_pending: u64,
+    },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TrackedNonceManager {
+    state: Arc<Mutex<NonceState>>,
+}
+
+#[async_trait]
+impl NonceManager for TrackedNonceManager {
+    async fn get_next_nonce<P, N>(&self, provider: &P, address: Address) -> TransportResult<u64>
+    where
+        P: Provider<N>,
+        N: Network,
+    {
+        let mut state = self.state.lock().await;
+        let nonce = match state.next() {
+            Some(nonce) => nonce,
+            None => provider.get_transaction_count(address).pending().await?,
+        };
+        state.last_used = Some(nonce);
+        Ok(nonce)
+    }
+}
+
+impl TrackedNonceManager {
+    pub async fn state(&self) -> NonceState {
+        *self.state.lock().await
+    }
+
+    /// Compare the cached nonce with the chain's pending nonce
+    pub async fn drift(&self, chain_pending: u64) -> NonceDrift {
+        let mut state = self.state.lock().await;
+        state.chain_pending = Some(chain_pending);
+
+        match state.next() {
+            Some(next) if next < chain_pending => NonceDrift::Behind {
+                next,
+                chain_pending,
+            },
+            Some(next) if next > chain_pending => NonceDrift::Ahead {
+                next,
+                chain_pending,
+            },
+            _ => {
+                state.suspected_gap = None;
+                NonceDrift::InSync
+            }
+        }
+    }
+
+    /// Continue from the chain's pending nonce
+    pub async fn resync(&self, chain_pending: u64) {
+        let mut state = self.state.lock().await;
+        state.last_used = chain_pending.checked_sub(1);
+        state.suspected_gap = None;
+        state.resyncs += 1;
+    }
+
+    /// Record a gap and return whether it was already there at the previous check
+    pub async fn confirm_gap(&self, next: u64, chain_pending: u64) -> bool {
+        let mut state = self.state.lock().await;
+        let confirmed = state.suspected_gap == Some((chain_pending, next));
+        state.suspected_gap = if confirmed {
+            None
+        } else {
+            Some((chain_pending, next))
+        };
+        confirmed
+    }
+
+    /// Hand out the next nonce like the filler does, for a transaction that sets its own
+    pub async fn next_nonce(
+        &self,
+        provider: &(dyn Provider<Ethereum> + Send + Sync),
+        address: Address,
+    ) -> TransportResult<u64> {
+        let mut state = self.state.lock().await;
+        let nonce = match state.next() {
+            Some(nonce) => nonce,
+            None => provider.get_transaction_count(address).pending().await?,
+        };
+        state.last_used = Some(nonce);
+        Ok(nonce)
+    }
+
+    /// Record `nonce` as used by a transaction that set its own nonce
+    pub async fn reserve(&self, nonce: u64) {
+        let mut state = self.state.lock().await;
+        if state.last_used.is_none_or(|last_used| last_used < nonce) {
+            state.last_used = Some(nonce);
+        }
+    }
+
+    pub async fn record_gaps_filled(&self, count: u64) {
+        self.state.lock().await.gaps_filled += count;
+    }
+}
@@ -0,0 +1,157 @@
+//! RPC endpoints shared by the relayer.
+//!
+//! Requests go through an alloy `FallbackService` over every configured endpoint: each
+//! read is raced on the `active_transports` best-scored endpoints, scored on success rate
+//! and latency, and broadcasts are tried one endpoint at a time in score order. Until
+//! scores diverge the configured order is kept. Critical reads (balances and
+//! `getRandomness` checks, canonical block hashes) can additionally require `quorum`
+//! endpoints to agree. Endpoints answer at different heads, so a quorum read is pinned
+//! to the lowest head among the endpoints that report one.
+
+use super::config::RpcConfig;
+use super::metrics;
+use alloy::{
+    eips::BlockId,
+    network::Ethereum,
+    providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::client::RpcClient,
+    transports::{http::ReqwestTransport, layers::FallbackService, TransportError},
+};
+use futures::future::join_all;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+pub struct RpcPool {
+    client: RpcClient,
+    // Read-only provider over the fallback client
+    provider: Arc<dyn Provider<Ethereum> + Send + Sync>,
+    // One provider per endpoint, for quorum reads
+    endpoints: Vec<Arc<dyn Provider<Ethereum> + Send + Sync>>,
+    quorum: usize,
+}
+
+impl RpcPool {
+    pub fn new(config: &RpcConfig) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut transports = Vec::with_capacity(config.urls.len());
+        let mut endpoints: Vec<Arc<dyn Provider<Ethereum> + Send + Sync>> = Vec::new();
+        for url in config.urls.iter() {
+            let url = url
+                .parse()
+                .map_err(|_| format!("Invalid RPC endpoint URL: {url}"))?;
+            transports.push(ReqwestTransport::new(url));
+        }
+        for transport in transports.iter() {
+            endpoints.push(Arc::new(RootProvider::new(RpcClient::new(
+                transport.clone(),
+                transport.guess_local(),
+            ))));
+        }
+
+        let is_local = transports.iter().all(|transport| transport.guess_local());
+        // Parallel broadcasts would surface "already known" from the slower endpoints
+        let service = FallbackService::new(transports, config.active_transports)
+            .append_sequential_method("eth_sendRawTransaction");
+        let client = RpcClient::new(service, is_local);
+
+        info!(
+            "Using {} RPC endpoints ({} active, quorum {})",
+            config.urls.len(),
+            config.active_transports,
+            config.quorum
+        );
+
+        Ok(Self {
+            provider: Arc::new(ProviderBuilder::new().connect_client(client.clone())),
+            client,
+            endpoints,
+            quorum: config.quorum,
+        })
+    }
+
+    /// Client failing over between the endpoints, to build signing providers on
+    pub fn client(&self) -> RpcClient {
+        self.client.clone()
+    }
+
+    /// Latest block number, through the fallback client
+    pub async fn block_number(&self) -> Result<u64, TransportError> {
+        self.provider.get_block_number().await
+    }
+
+    /// Run `read` on every endpoint and return the value at least `quorum` of them agree on.
+    ///
+    /// `read` gets the block to read at: the lowest head of the endpoints, so that they all
+    /// answer for the same state. With a quorum of 1, `read` runs once through the
+    /// fallback client at the latest block instead.
+    pub async fn quorum<T, F, Fut>(
+        &self,
+        label: &'static str,
+        read: F,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
+    where
+        T: PartialEq + Debug,
+        F: Fn(Arc<dyn Provider<Ethereum> + Send + Sync>, BlockId) -> Fut,
+        Fut: Future<Output = Result<T, TransportError>>,
+    {
+        if self.quorum <= 1 {
+            return Ok(read(self.provider.clone(), BlockId::latest()).await?);
+        }
+
+        let heads: Vec<u64> = join_all(
+            self.endpoints
+                .iter()
+                .map(|endpoint| async move { endpoint.get_block_number().await }),
+        )
+        .await
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect();
+        if heads.len() < self.quorum {
+            metrics::record_quorum_failure(label);
+            return Err(format!(
+                "RPC quorum of {} not reached for {label}: only {} endpoints report their head",
+                self.quorum,
+                heads.len()
+            )
+            .into());
+        }
+        let block = BlockId::number(heads.into_iter().min().unwrap_or_default());
+
+        let results = join_all(
+            self.endpoints
+                .iter()
+                .map(|endpoint| read(endpoint.clone(), block)),
+        )
+        .await;
+
+        let mut votes: Vec<(T, usize)> = Vec::new();
+        let mut errors = 0;
+        for result in results {
+            match result {
+                Ok(value) => match votes.iter_mut().find(|(voted, _)| *voted == value) {
+                    Some((_, count)) => *count += 1,
+                    None => votes.push((value, 1)),
+                },
+                Err(_) => errors += 1,
+            }
+        }
+
+        if let Some(idx) = votes.iter().position(|(_, count)| *count >= self.quorum) {
+            return Ok(votes.swap_remove(idx).0);
+        }
+
+        warn!(
+            "RPC quorum of {} not reached for {} at block {}: answers {:?}, {} errors",
+            self.quorum, label, block, votes, errors
+        );
+        metrics::record_quorum_failure(label);
+        Err(format!(
+            "RPC quorum of {} not reached for {label} ({} distinct answers, {errors} errors)",
+            self.quorum,
+            votes.len()
+        )
+        .into())
+    }
+}
@@ -1,26 +1,51 @@
 use super::{
-    account::RelayerAccount,
-    config::{RelayerConfig, SchedulerType},
-    metrics, SkipReason,
+    account::{self, RelayerAccount},
+    batch_sizer::BatchSizer,
+    config::{FulfillmentMode, RelayerConfig},
+    funder::Funder,
+    gas,
+    lifecycle::TxLifecycleConfig,
+    metrics,
+    rpc::RpcPool,
+    selection::{self, Scheduler},
+    signer::{AccountSigner, RemoteSigner},
+    Availability, SkipReason,
 };
-use alloy::primitives::{Address, U256};
-use rand::Rng;
+use crate::database::QueueDatabase;
+use crate::oracle::{self, CallFailure};
+use alloy::primitives::{Address, Bytes, B256, U256};
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol_types::SolCall;
+use futures::future::join_all;
 use std::collections::HashSet;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tracing::{info, span, trace, warn, Level};
+use tracing::{debug, info, span, trace, warn, Level};
+
+const NONCE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a key's BEBE delegation may take before the key is left out
+const DELEGATION_TIMEOUT: Duration = Duration::from_secs(120);
 
 /// Main relayer struct that manages multiple accounts
 pub struct Relayer {
     pub accounts: Vec<Arc<RelayerAccount>>,
-    scheduler_type: SchedulerType,
+    scheduler: Arc<dyn Scheduler>,
     pending_block_threshold: u64,
-    round_robin_index: AtomicUsize,
-    rpc_url: String,
     pub batch_size: usize,
+    pub batch_sizer: Arc<BatchSizer>,
+    pub isolate_failures: bool,
+    confirmation_depth: u64,
+    rpc: Arc<RpcPool>,
+    // Tops up low accounts when a treasury is configured
+    funder: Option<Arc<Funder>>,
     // Track accounts currently in use for batch processing
     accounts_in_use: Arc<Mutex<HashSet<Address>>>,
+    // Keys this replica may sign with, see `set_owned`
+    owned_keys: Arc<Mutex<HashSet<Address>>>,
+    // Accounts whose BEBE delegation is in place, see `delegate`
+    delegated: Arc<Mutex<HashSet<Address>>>,
 }
 
 impl Relayer {
@@ -30,8 +55,7 @@ impl Relayer {
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         // Initialize metrics
         metrics::init_metrics();
-        let rpc_url =
-            std::env::var("RPC_URL").unwrap_or_else(|_| "http://127.0.0.1:8545".to_string());
+        let rpc = Arc::new(RpcPool::new(&config.rpc)?);
 
         // Parse BEBE address if provided
         let bebe_address = if let Some(bebe_str) = &config.bebe_address {
@@ -44,18 +68,44 @@ impl Relayer {
             None
         };
 
+        let lifecycle_config = TxLifecycleConfig {
+            stuck_after_blocks: config.stuck_tx_blocks,
+            fee_bump_percent: config.fee_bump_percent,
+            max_fee_per_gas: config.max_fee_per_gas_wei,
+            timeout_blocks: config.tx_timeout_blocks,
+        };
+
+        let gas_strategy = gas::from_config(&config.gas_strategy)?;
+
+        // Local keys first, then the keys listed by the remote signer
+        let mut signers = Vec::new();
+        for account_config in config.accounts.iter() {
+            signers.push((
+                AccountSigner::Local(account_config.key.signer()?),
+                &account_config.min_gas_wei,
+            ));
+        }
+        if let Some(remote) = &config.remote_signer {
+            for signer in RemoteSigner::connect(&remote.url, remote.protocol).await? {
+                signers.push((AccountSigner::Remote(signer), &remote.min_gas_wei));
+            }
+        }
+
         // Initialize accounts
         let mut accounts = Vec::new();
-        for (idx, account_config) in config.accounts.iter().enumerate() {
+        for (idx, (signer, min_gas_wei)) in signers.into_iter().enumerate() {
             info!("Initializing relayer account {}", idx);
 
-            let min_gas_balance = U256::from_str_radix(&account_config.min_gas_wei, 10)?;
+            let min_gas_balance = U256::from_str_radix(min_gas_wei, 10)?;
             let account = Arc::new(
                 RelayerAccount::new(
-                    &account_config.private_key,
-                    &rpc_url,
+                    signer,
+                    rpc.clone(),
                     min_gas_balance,
                     bebe_address,
+                    config.fulfillment_mode,
+                    lifecycle_config.clone(),
+                    gas_strategy.clone(),
                 )
                 .await?,
             );
@@ -64,10 +114,10 @@ impl Relayer {
                 "Initialized account {} with address {}{}",
                 idx,
                 account.address,
-                if bebe_address.is_some() {
-                    " (BEBE enabled)"
-                } else {
-                    ""
+                match config.fulfillment_mode {
+                    FulfillmentMode::Batch => " (BEBE enabled)",
+                    FulfillmentMode::Single => " (single transactions)",
+                    FulfillmentMode::Multicall3(_) => " (Multicall3)",
                 }
             );
 
@@ -78,47 +128,114 @@ impl Relayer {
             return Err("No relayer accounts configured".into());
         }
 
+        // Never plan batches larger than a block
+        let block_gas_limit = match accounts[0].block_gas_limit().await {
+            Ok(block_gas_limit) => Some(block_gas_limit),
+            Err(e) => {
+                warn!("Failed to fetch block gas limit: {}", e);
+                None
+            }
+        };
+        let batch_sizer =
+            BatchSizer::new(config.batch_size, config.batch_gas_budget, block_gas_limit);
+        info!("Batch gas budget: {}", batch_sizer.gas_budget());
+
+        let funder = match &config.treasury {
+            Some(treasury) => {
+                let funder = Funder::new(
+                    treasury,
+                    &rpc,
+                    gas_strategy.clone(),
+                    config.max_fee_per_gas_wei,
+                )?;
+                info!("Treasury top-ups enabled from {}", funder.address);
+                Some(Arc::new(funder))
+            }
+            None => None,
+        };
+
+        let scheduler = selection::from_config(&config.scheduler);
         info!(
             "Relayer initialized with {} accounts using {} scheduler",
             accounts.len(),
-            match config.scheduler {
-                SchedulerType::RoundRobin => "round-robin",
-                SchedulerType::Random => "random",
-            }
+            scheduler.name()
         );
 
         Ok(Self {
             accounts,
-            scheduler_type: config.scheduler,
+            scheduler,
             pending_block_threshold: config.pending_block_threshold,
-            round_robin_index: AtomicUsize::new(0),
-            rpc_url,
             batch_size: config.batch_size,
+            batch_sizer: Arc::new(batch_sizer),
+            isolate_failures: config.isolate_failures,
+            confirmation_depth: config.confirmation_depth,
+            rpc,
+            funder,
             accounts_in_use: Arc::new(Mutex::new(HashSet::new())),
+            owned_keys: Arc::new(Mutex::new(HashSet::new())),
+            delegated: Arc::new(Mutex::new(HashSet::new())),
         })
     }
 
-    /// Round-robin selection
-    async fn select_round_robin(&self) -> Arc<RelayerAccount> {
-        let index = self.round_robin_index.fetch_add(1, Ordering::Relaxed) % self.accounts.len();
-        self.accounts[index].clone()
-    }
+    /// Delegate the accounts of `addresses` to BEBE, all at once.
+    ///
+    /// An account whose delegation fails or times out is skipped as `BebeMissing` until a
+    /// later call delegates it, instead of holding up the others.
+    pub async fn delegate(&self, addresses: &[Address]) {
+        let accounts = self
+            .accounts
+            .iter()
+            .filter(|account| addresses.contains(&account.address));
+
+        let results = join_all(accounts.map(|account| async move {
+            let result =
+                tokio::time::timeout(DELEGATION_TIMEOUT, account.ensure_delegation()).await;
+            (account.address, result)
+        }))
+        .await;
 
-    /// Random selection
-    async fn select_random(&self) -> Arc<RelayerAccount> {
-        let mut rng = rand::thread_rng();
-        let index = rng.gen_range(0..self.accounts.len());
-        self.accounts[index].clone()
+        let mut delegated = self.delegated.lock().await;
+        for (address, result) in results {
+            match result {
+                Ok(Ok(())) => {
+                    delegated.insert(address);
+                }
+                Ok(Err(e)) => {
+                    warn!("Failed to delegate account {} to BEBE: {}", address, e);
+                    delegated.remove(&address);
+                }
+                Err(_) => {
+                    warn!(
+                        "Delegation of account {} to BEBE timed out after {:?}",
+                        address, DELEGATION_TIMEOUT
+                    );
+                    delegated.remove(&address);
+                }
+            }
+        }
     }
 
-    /// Determine why an account was skipped
-    async fn determine_skip_reason(
-        &self,
-        account: &RelayerAccount,
-    ) -> Result<SkipReason, Box<dyn std::error::Error + Send + Sync>> {
-        // The account's is_available method already checks balance
-        // If we're here, it's likely due to pending transactions or recent failure
-        Ok(SkipReason::PendingTransaction)
+    /// Log and count a skipped account
+    fn record_skip(&self, account: &RelayerAccount, reason: &SkipReason) {
+        span!(
+            Level::DEBUG,
+            "relayer.skip",
+            address = %account.address,
+            reason = %reason
+        )
+        .in_scope(|| match reason {
+            SkipReason::InUse => trace!("Account {} is already in use, skipping", account.address),
+            SkipReason::NotOwned => trace!(
+                "Account {} is owned by another replica, skipping",
+                account.address
+            ),
+            SkipReason::BebeMissing => warn!(
+                "Account {} selected but not delegated to BEBE",
+                account.address
+            ),
+            reason => debug!("Skipping account {} (reason: {})", account.address, reason),
+        });
+        metrics::record_skip(&account.address.to_string(), reason.label());
     }
 
     /// Get addresses of all managed accounts
@@ -126,49 +243,107 @@ impl Relayer {
         self.accounts.iter().map(|a| a.address).collect()
     }
 
+    /// Highest block with the configured confirmation depth on top of it
+    pub async fn confirmed_block(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let head = self.rpc.block_number().await?;
+        Ok(head.saturating_sub(self.confirmation_depth))
+    }
+
+    /// Hash of the canonical block at `number`
+    pub async fn canonical_block_hash(
+        &self,
+        number: u64,
+    ) -> Result<B256, Box<dyn std::error::Error + Send + Sync>> {
+        // The block is read by number, the quorum's pinned block is not needed
+        self.rpc
+            .quorum("block_hash", |provider, _| async move {
+                let block = provider.get_block_by_number(number.into()).await?;
+                Ok(block.map(|block| block.header.hash))
+            })
+            .await?
+            .ok_or_else(|| format!("Block {number} not found").into())
+    }
+
+    /// Whether the oracle at `contract` has request `request_id` fulfilled, or `None`
+    /// when it does not know the request
+    pub async fn request_state(
+        &self,
+        contract: Address,
+        request_id: B256,
+    ) -> Result<Option<bool>, Box<dyn std::error::Error + Send + Sync>> {
+        let data = oracle::encode_get_randomness_call(request_id).abi_encode();
+        let tx = TransactionRequest::default()
+            .to(contract)
+            .input(Bytes::from(data).into());
+
+        // A revert is an answer too, the endpoints have to agree on it
+        let output = self
+            .rpc
+            .quorum("eth_call", |provider, block| {
+                let tx = tx.clone();
+                async move {
+                    match provider.call(tx).block(block).await {
+                        Ok(output) => Ok(Ok(output)),
+                        Err(e) => account::revert_data(&e).map(Err).ok_or(e),
+                    }
+                }
+            })
+            .await?;
+
+        match output {
+            Ok(output) => {
+                let state = oracle::IVRFOracle::getRandomnessCall::abi_decode_returns(&output)?;
+                Ok(Some(state.fulfilled))
+            }
+            Err(revert_data) => match oracle::decode_revert(&revert_data) {
+                CallFailure::RequestNotFound => Ok(None),
+                failure => Err(format!("getRandomness failed: {failure}").into()),
+            },
+        }
+    }
+
+    /// Addresses of every key this relayer signs with, the treasury included
+    pub fn key_addresses(&self) -> Vec<Address> {
+        let mut addresses = self.get_addresses();
+        if let Some(funder) = &self.funder {
+            addresses.push(funder.address);
+        }
+        addresses
+    }
+
+    /// Allow or stop signing with the key of `address`.
+    ///
+    /// No key is owned at first: accounts are only handed out, and the treasury only tops
+    /// up, once the queue processor has taken ownership of their keys.
+    pub async fn set_owned(&self, address: Address, owned: bool) {
+        let mut owned_keys = self.owned_keys.lock().await;
+        if owned {
+            owned_keys.insert(address);
+        } else {
+            owned_keys.remove(&address);
+        }
+    }
+
     /// Get next available account for batch sending
     pub async fn next_available_batch(
         &self,
     ) -> Result<Arc<RelayerAccount>, Box<dyn std::error::Error + Send + Sync>> {
-        let mut attempts = 0;
-        let max_attempts = self.accounts.len() * 3; // More attempts since we check for in-use
-
-        while attempts < max_attempts {
-            attempts += 1;
-
-            // Select next account based on scheduler
-            let account = match self.scheduler_type {
-                SchedulerType::RoundRobin => self.select_round_robin().await,
-                SchedulerType::Random => self.select_random().await,
-            };
-
-            // Check if account is already in use
-            {
-                let in_use = self.accounts_in_use.lock().await;
-                if in_use.contains(&account.address) {
-                    trace!("Account {} is already in use, skipping", account.address);
-                    continue;
-                }
+        // More attempts since accounts may be released in the meantime
+        for _ in 0..3 {
+            if let Some(account) = self.try_get_available_batch().await {
+                return Ok(account);
             }
+        }
 
-            // Check if account is available
-            match account.is_available(self.pending_block_threshold).await {
-                Ok(true) => {
-                    // Check if account has BEBE configured
-                    if account.bebe_address.is_none() {
-                        warn!(
-                            "Account {} selected but BEBE not configured",
-                            account.address
-                        );
-                        continue;
-                    }
-
-                    // Mark account as in use
-                    {
-                        let mut in_use = self.accounts_in_use.lock().await;
-                        in_use.insert(account.address);
-                    }
+        Err("No available relayer accounts".into())
+    }
 
+    /// Try to get an available account for batch sending without blocking
+    pub async fn try_get_available_batch(&self) -> Option<Arc<RelayerAccount>> {
+        // Try each account once, in the scheduler's order
+        for account in self.scheduler.order(&self.accounts).await {
+            match self.claim(&account).await {
+                Availability::Available => {
                     span!(
                         Level::INFO,
                         "relayer.select_batch",
@@ -179,121 +354,120 @@ impl Relayer {
                     });
 
                     metrics::record_selection(&account.address.to_string());
-                    return Ok(account);
+                    return Some(account);
                 }
-                Ok(false) => {
-                    let reason = self.determine_skip_reason(&account).await?;
-                    span!(
-                        Level::WARN,
-                        "relayer.skip",
-                        address = %account.address,
-                        reason = %reason
-                    )
-                    .in_scope(|| {
+                Availability::Skipped(reason) => {
+                    if let SkipReason::RpcError(e) = &reason {
                         warn!(
-                            "Skipping account {} (reason: {:?})",
-                            account.address, reason
+                            "Error checking account {} availability: {}",
+                            account.address, e
                         );
-                    });
-                    metrics::record_skip(&account.address.to_string(), &reason.to_string());
-                }
-                Err(e) => {
-                    warn!(
-                        "Error checking account {} availability: {}",
-                        account.address, e
-                    );
-                    metrics::record_skip(
-                        &account.address.to_string(),
-                        &SkipReason::RecentFailure.to_string(),
-                    );
+                    }
+                    self.record_skip(&account, &reason);
                 }
             }
         }
 
-        Err("No available relayer accounts with BEBE configured".into())
+        None
     }
 
-    /// Try to get an available account for batch sending without blocking
-    pub async fn try_get_available_batch(&self) -> Option<Arc<RelayerAccount>> {
-        // Try each account once
-        for _ in 0..self.accounts.len() {
-            // Select next account based on scheduler
-            let account = match self.scheduler_type {
-                SchedulerType::RoundRobin => self.select_round_robin().await,
-                SchedulerType::Random => self.select_random().await,
-            };
-
-            // Check if account is already in use
-            {
-                let in_use = self.accounts_in_use.lock().await;
-                if in_use.contains(&account.address) {
-                    trace!("Account {} is already in use, skipping", account.address);
-                    continue;
-                }
-            }
+    /// Accounts not in use that `claim` could hand out: owned by this replica, and
+    /// delegated when batching. The balance and cooldown checks are left to `claim`.
+    pub async fn claimable_accounts(&self) -> usize {
+        let in_use = self.accounts_in_use.lock().await.clone();
+        let owned = self.owned_keys.lock().await.clone();
+        let delegated = self.delegated.lock().await.clone();
 
-            // Check if account is available
-            match account.is_available(self.pending_block_threshold).await {
-                Ok(true) => {
-                    // Check if account has BEBE configured
-                    if account.bebe_address.is_none() {
-                        warn!(
-                            "Account {} selected but BEBE not configured",
-                            account.address
-                        );
-                        continue;
-                    }
+        self.accounts
+            .iter()
+            .filter(|account| {
+                !in_use.contains(&account.address)
+                    && owned.contains(&account.address)
+                    && (account.fulfillment_mode != FulfillmentMode::Batch
+                        || (account.bebe_address.is_some() && delegated.contains(&account.address)))
+            })
+            .count()
+    }
 
-                    // Mark account as in use
-                    {
-                        let mut in_use = self.accounts_in_use.lock().await;
-                        in_use.insert(account.address);
-                    }
+    /// Mark an account as in use if it can take a batch
+    async fn claim(&self, account: &RelayerAccount) -> Availability {
+        // Check if account is already in use
+        if self.accounts_in_use.lock().await.contains(&account.address) {
+            return Availability::Skipped(SkipReason::InUse);
+        }
 
-                    span!(
-                        Level::INFO,
-                        "relayer.select_batch",
-                        address = %account.address
-                    )
-                    .in_scope(|| {
-                        trace!("Selected account {} for batch", account.address);
-                    });
+        // Another replica may be signing with this key
+        if !self.owned_keys.lock().await.contains(&account.address) {
+            return Availability::Skipped(SkipReason::NotOwned);
+        }
 
-                    metrics::record_selection(&account.address.to_string());
-                    return Some(account);
+        // Batching through BEBE needs the delegation target, and the delegation in place
+        if account.fulfillment_mode == FulfillmentMode::Batch
+            && (account.bebe_address.is_none()
+                || !self.delegated.lock().await.contains(&account.address))
+        {
+            return Availability::Skipped(SkipReason::BebeMissing);
+        }
+
+        // Check if account is available
+        let availability = account.is_available(self.pending_block_threshold).await;
+        if let Availability::Available = availability {
+            self.accounts_in_use.lock().await.insert(account.address);
+        }
+        availability
+    }
+
+    /// Periodically check every account's nonce against the chain
+    pub fn spawn_nonce_monitor(self: &Arc<Self>) {
+        let relayer = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(NONCE_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+
+                // Keys held by another replica are left to it, gap fills send transactions
+                let owned = relayer.owned_keys.lock().await.clone();
+
+                // Retry the delegations that failed
+                let undelegated: Vec<_> = {
+                    let delegated = relayer.delegated.lock().await;
+                    owned
+                        .iter()
+                        .filter(|address| !delegated.contains(*address))
+                        .copied()
+                        .collect()
+                };
+                if !undelegated.is_empty() {
+                    relayer.delegate(&undelegated).await;
                 }
-                Ok(false) => {
-                    if let Ok(reason) = self.determine_skip_reason(&account).await {
-                        span!(
-                            Level::DEBUG,
-                            "relayer.skip",
-                            address = %account.address,
-                            reason = %reason
-                        )
-                        .in_scope(|| {
-                            trace!(
-                                "Skipping account {} (reason: {:?})",
-                                account.address,
-                                reason
-                            );
-                        });
-                        metrics::record_skip(&account.address.to_string(), &reason.to_string());
+
+                for account in relayer.accounts.iter() {
+                    if owned.contains(&account.address) {
+                        if let Err(e) = account.sync_nonce().await {
+                            warn!("Failed to check nonce of {}: {}", account.address, e);
+                        }
                     }
-                }
-                Err(e) => {
-                    warn!(
-                        "Error checking account {} availability: {}",
-                        account.address, e
-                    );
-                    metrics::record_skip(
+                    metrics::record_account_metrics(
                         &account.address.to_string(),
-                        &SkipReason::RecentFailure.to_string(),
+                        &account.get_metrics().await,
                     );
                 }
             }
+        });
+    }
+
+    /// Top up low accounts from the treasury, if one is configured
+    pub fn spawn_funder(&self, queue_db: QueueDatabase) {
+        if let Some(funder) = &self.funder {
+            funder
+                .clone()
+                .spawn(self.accounts.clone(), self.owned_keys.clone(), queue_db);
         }
+    }
 
-        None
+    /// Hold an account for a batch that was not handed out by the scheduler
+    pub async fn hold_account(&self, address: Address) {
+        self.accounts_in_use.lock().await.insert(address);
     }
 
     /// Release an account after batch processing
@@ -0,0 +1,108 @@
+//! Account selection policies.
+//!
+//! A [`Scheduler`] only decides the order in which accounts are tried; the
+//! [`Relayer`](super::Relayer) walks that order and takes the first account that is not
+//! in use and passes its availability checks.
+
+use super::account::RelayerAccount;
+use super::config::SchedulerType;
+use async_trait::async_trait;
+use rand::seq::SliceRandom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[async_trait]
+pub trait Scheduler: Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Accounts in the order they should be tried
+    async fn order(&self, accounts: &[Arc<RelayerAccount>]) -> Vec<Arc<RelayerAccount>>;
+}
+
+/// Each selection starts from the account after the previous starting point
+#[derive(Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+#[async_trait]
+impl Scheduler for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round-robin"
+    }
+
+    async fn order(&self, accounts: &[Arc<RelayerAccount>]) -> Vec<Arc<RelayerAccount>> {
+        let mut order = accounts.to_vec();
+        if !order.is_empty() {
+            let start = self.next.fetch_add(1, Ordering::Relaxed) % order.len();
+            order.rotate_left(start);
+        }
+        order
+    }
+}
+
+/// Uniformly shuffled order
+pub struct Random;
+
+#[async_trait]
+impl Scheduler for Random {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    async fn order(&self, accounts: &[Arc<RelayerAccount>]) -> Vec<Arc<RelayerAccount>> {
+        let mut order = accounts.to_vec();
+        order.shuffle(&mut rand::thread_rng());
+        order
+    }
+}
+
+/// Accounts with the fewest in-flight transactions first
+pub struct LeastPending;
+
+#[async_trait]
+impl Scheduler for LeastPending {
+    fn name(&self) -> &'static str {
+        "least-pending"
+    }
+
+    async fn order(&self, accounts: &[Arc<RelayerAccount>]) -> Vec<Arc<RelayerAccount>> {
+        let mut order = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            order.push((account.pending_tx_count().await, account.clone()));
+        }
+        // Stable sort, ties keep the configured order
+        order.sort_by_key(|(pending, _)| *pending);
+        order.into_iter().map(|(_, account)| account).collect()
+    }
+}
+
+/// Accounts with the most balance above `min_gas_balance` first, so gas drains evenly
+pub struct BalanceWeighted;
+
+#[async_trait]
+impl Scheduler for BalanceWeighted {
+    fn name(&self) -> &'static str {
+        "balance-weighted"
+    }
+
+    async fn order(&self, accounts: &[Arc<RelayerAccount>]) -> Vec<Arc<RelayerAccount>> {
+        let mut order = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            order.push((account.gas_headroom().await, account.clone()));
+        }
+        order.sort_by(|(a, _), (b, _)| b.cmp(a));
+        order.into_iter().map(|(_, account)| account).collect()
+    }
+}
+
+/// Build the configured scheduler
+pub fn from_config(scheduler_type: &SchedulerType) -> Arc<dyn Scheduler> {
+    match scheduler_type {
+        SchedulerType::RoundRobin => Arc::new(RoundRobin::default()),
+        SchedulerType::Random => Arc::new(Random),
+        SchedulerType::LeastPending => Arc::new(LeastPending),
+        SchedulerType::BalanceWeighted => Arc::new(BalanceWeighted),
+    }
+}
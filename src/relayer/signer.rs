@@ -0,0 +1,248 @@
+//! Transaction signers for relayer accounts.
+//!
+//! Accounts sign either with a local key or through a remote signer, so hot keys can
+//! stay out of the oracle process. Remote signers speak the Web3Signer `eth1` API
+//! (`/api/v1/eth1/publicKeys`, `/api/v1/eth1/sign/{publicKey}`) or plain JSON-RPC
+//! (`eth_accounts`, `eth_signTransaction`); account addresses come from their key listing.
+
+use alloy::{
+    consensus::{SignableTransaction, TxEnvelope},
+    eips::{
+        eip2718::Decodable2718,
+        eip7702::{constants::MAGIC, Authorization, SignedAuthorization},
+    },
+    network::TxSigner,
+    primitives::{keccak256, Address, Bytes, Signature},
+    rlp::Encodable,
+    rpc::{client::RpcClient, types::TransactionRequest},
+    signers::{self, local::PrivateKeySigner, SignerSync},
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Remote signing protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteSignerProtocol {
+    /// Web3Signer `eth1` REST API, signs raw payloads
+    Web3Signer,
+    /// `eth_signTransaction` over JSON-RPC, cannot sign EIP-7702 authorizations
+    JsonRpc,
+}
+
+/// Signer of a relayer account
+#[derive(Clone)]
+pub enum AccountSigner {
+    Local(PrivateKeySigner),
+    Remote(RemoteSigner),
+}
+
+impl AccountSigner {
+    /// Sign an EIP-7702 authorization for this account
+    pub async fn sign_authorization(
+        &self,
+        authorization: Authorization,
+    ) -> Result<SignedAuthorization, Box<dyn std::error::Error + Send + Sync>> {
+        let signature = match self {
+            Self::Local(signer) => signer.sign_hash_sync(&authorization.signature_hash())?,
+            Self::Remote(signer) => {
+                let mut payload = vec![MAGIC];
+                authorization.encode(&mut payload);
+                signer.sign_payload(&payload).await?
+            }
+        };
+
+        Ok(authorization.into_signed(signature))
+    }
+}
+
+#[async_trait]
+impl TxSigner<Signature> for AccountSigner {
+    fn address(&self) -> Address {
+        match self {
+            Self::Local(signer) => signer.address(),
+            Self::Remote(signer) => signer.address,
+        }
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> signers::Result<Signature> {
+        match self {
+            Self::Local(signer) => signer.sign_transaction(tx).await,
+            Self::Remote(signer) => signer.sign_transaction(tx).await,
+        }
+    }
+}
+
+/// One account held by a remote signer
+#[derive(Clone)]
+pub struct RemoteSigner {
+    pub address: Address,
+    /// Uncompressed public key, the Web3Signer key identifier
+    public_key: Option<String>,
+    url: String,
+    protocol: RemoteSignerProtocol,
+    http: reqwest::Client,
+    rpc: RpcClient,
+}
+
+impl RemoteSigner {
+    /// One signer per key listed by the remote signer at `url`
+    pub async fn connect(
+        url: &str,
+        protocol: RemoteSignerProtocol,
+    ) -> Result<Vec<Self>, Box<dyn std::error::Error + Send + Sync>> {
+        let url = url.trim_end_matches('/').to_string();
+        let http = reqwest::Client::new();
+        let rpc = RpcClient::new_http(url.parse()?);
+
+        let keys: Vec<(Address, Option<String>)> = match protocol {
+            RemoteSignerProtocol::Web3Signer => {
+                let public_keys: Vec<String> = http
+                    .get(format!("{url}/api/v1/eth1/publicKeys"))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                public_keys
+                    .into_iter()
+                    .map(|public_key| {
+                        let bytes = hex::decode(public_key.trim_start_matches("0x"))
+                            .map_err(|_| format!("Invalid public key from signer: {public_key}"))?;
+                        // Keys are listed uncompressed, with or without the 0x04 prefix
+                        let raw = match bytes.len() {
+                            65 => &bytes[1..],
+                            64 => &bytes[..],
+                            _ => {
+                                return Err(format!("Invalid public key from signer: {public_key}"))
+                            }
+                        };
+                        Ok((Address::from_raw_public_key(raw), Some(public_key)))
+                    })
+                    .collect::<Result<_, String>>()?
+            }
+            RemoteSignerProtocol::JsonRpc => {
+                let accounts: Vec<Address> = rpc.request_noparams("eth_accounts").await?;
+                accounts
+                    .into_iter()
+                    .map(|address| (address, None))
+                    .collect()
+            }
+        };
+
+        info!("Remote signer at {} holds {} keys", url, keys.len());
+
+        Ok(keys
+            .into_iter()
+            .map(|(address, public_key)| Self {
+                address,
+                public_key,
+                url: url.clone(),
+                protocol,
+                http: http.clone(),
+                rpc: rpc.clone(),
+            })
+            .collect())
+    }
+
+    /// Sign `keccak256(payload)` through the Web3Signer `eth1` API
+    async fn sign_payload(&self, payload: &[u8]) -> signers::Result<Signature> {
+        let Some(public_key) = &self.public_key else {
+            return Err(signers::Error::message(
+                "the JSON-RPC signer cannot sign raw payloads",
+            ));
+        };
+
+        #[derive(Serialize)]
+        struct SignRequest {
+            data: Bytes,
+        }
+
+        let signature = self
+            .http
+            .post(format!("{}/api/v1/eth1/sign/{}", self.url, public_key))
+            .json(&SignRequest {
+                data: Bytes::copy_from_slice(payload),
+            })
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(signers::Error::other)?
+            .text()
+            .await
+            .map_err(signers::Error::other)?;
+
+        let signature = hex::decode(signature.trim().trim_matches('"').trim_start_matches("0x"))
+            .map_err(signers::Error::other)?;
+        let signature = Signature::from_raw(&signature)?;
+
+        // Never hand out a signature from a different key
+        let recovered = signature
+            .recover_address_from_prehash(&keccak256(payload))
+            .map_err(signers::Error::other)?;
+        if recovered != self.address {
+            return Err(signers::Error::message(format!(
+                "remote signer returned a signature from {recovered}, expected {}",
+                self.address
+            )));
+        }
+
+        Ok(signature)
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> signers::Result<Signature> {
+        match self.protocol {
+            RemoteSignerProtocol::Web3Signer => self.sign_payload(&tx.encoded_for_signing()).await,
+            RemoteSignerProtocol::JsonRpc => {
+                let request = self.transaction_request(tx);
+                let raw: Bytes = self
+                    .rpc
+                    .request("eth_signTransaction", (request,))
+                    .await
+                    .map_err(signers::Error::other)?;
+
+                let signed =
+                    TxEnvelope::decode_2718(&mut raw.as_ref()).map_err(signers::Error::other)?;
+                if signed.signature_hash() != tx.signature_hash() {
+                    return Err(signers::Error::message(
+                        "remote signer signed a different transaction",
+                    ));
+                }
+
+                Ok(*signed.signature())
+            }
+        }
+    }
+
+    /// JSON-RPC form of the transaction to sign
+    fn transaction_request(&self, tx: &dyn SignableTransaction<Signature>) -> TransactionRequest {
+        let mut request = TransactionRequest::default()
+            .from(self.address)
+            .nonce(tx.nonce())
+            .gas_limit(tx.gas_limit())
+            .value(tx.value())
+            .input(tx.input().clone().into());
+        request.to = Some(tx.kind());
+        request.chain_id = tx.chain_id();
+        request.transaction_type = Some(tx.ty());
+
+        if tx.is_dynamic_fee() {
+            request.max_fee_per_gas = Some(tx.max_fee_per_gas());
+            request.max_priority_fee_per_gas = tx.max_priority_fee_per_gas();
+        } else {
+            request.gas_price = tx.gas_price();
+        }
+        request.access_list = tx.access_list().cloned();
+        request.authorization_list = tx.authorization_list().map(|list| list.to_vec());
+
+        request
+    }
+}
@@ -149,9 +149,12 @@ async fn randomness_requested_handler(
                                 for result in results.iter() {
                                     let request_id = result.event_data.requestId;
                                     let contract_address = result.tx_information.address;
+                                    let requester = result.event_data.requester;
+                                    let block_hash = result.tx_information.block_hash;
+                                    let block_number = result.tx_information.block_number;
                                     let network = result.tx_information.network.to_string();
 
-                                    match queue_db.enqueue_request(request_id, contract_address, &network).await {
+                                    match queue_db.enqueue_request(request_id, contract_address, requester, block_hash, block_number, &network).await {
                                         Ok(_) => {
                                             trace!(
                                                 "Enqueued randomness request {} from contract {}",
@@ -0,0 +1,79 @@
+//! Graceful shutdown on SIGINT / SIGTERM.
+//!
+//! The first signal asks the queue processor to stop dequeuing and drain its in-flight
+//! batches for up to `SHUTDOWN_TIMEOUT_SECS`; a second one exits immediately.
+
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+#[derive(Clone)]
+pub struct Shutdown {
+    rx: watch::Receiver<bool>,
+    /// How long in-flight batches may take to finish once shutdown starts
+    pub drain_timeout: Duration,
+}
+
+impl Shutdown {
+    /// Listen for SIGINT and SIGTERM
+    pub fn listen() -> Self {
+        let drain_timeout = std::env::var("SHUTDOWN_TIMEOUT_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(60));
+
+        let (tx, rx) = watch::channel(false);
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            warn!(
+                "Shutdown requested, draining in-flight batches for up to {:?}",
+                drain_timeout
+            );
+            let _ = tx.send(true);
+
+            wait_for_signal().await;
+            error!("Second shutdown signal, exiting immediately");
+            std::process::exit(130);
+        });
+
+        Self { rx, drain_timeout }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Resolve once shutdown has been requested
+    pub async fn wait(&self) {
+        let mut rx = self.rx.clone();
+        // The sender only goes away if the signal task died, never shut down then
+        if rx.wait_for(|triggered| *triggered).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            error!("Failed to listen for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = sigterm.recv() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
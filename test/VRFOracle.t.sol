@@ -20,6 +20,8 @@ contract VRFOracleTest is Test {
 
     event RandomnessFulfilled(bytes32 indexed requestId, uint256 randomness);
 
+    event RandomnessProof(bytes32 indexed requestId, bytes proof);
+
     function setUp() public {
         vrfOracle = new VRFOracle(oracle, fee);
         vm.deal(user, 1 ether);
@@ -94,6 +96,32 @@ contract VRFOracleTest is Test {
         vm.stopPrank();
     }
 
+    /**
+     * @notice Test fulfilling with a proof emits both the fulfillment and the proof
+     */
+    function testFulfillRandomnessWithProofEmitsEvents() public {
+        vm.prank(user);
+        bytes32 requestId = vrfOracle.requestRandomness{value: fee}();
+
+        vm.startPrank(oracle);
+
+        uint256 randomValue = 87654321;
+        bytes memory proof = hex"02aabbcc";
+
+        vm.expectEmit(true, false, false, true);
+        emit RandomnessFulfilled(requestId, randomValue);
+        vm.expectEmit(true, false, false, true);
+        emit RandomnessProof(requestId, proof);
+
+        vrfOracle.fulfillRandomnessWithProof(requestId, randomValue, proof);
+
+        vm.stopPrank();
+
+        (bool fulfilled, uint256 randomness) = vrfOracle.getRandomness(requestId);
+        assertTrue(fulfilled);
+        assertEq(randomness, randomValue);
+    }
+
     /**
      * @notice Test overpayment still emits correct paid amount
      */